@@ -0,0 +1,35 @@
+//! Checks that no input makes `jsonish::parse` or the conversions applied to
+//! its result panic.
+//!
+//! Run from the repository root, seeded with the regression corpus:
+//!
+//! ```sh
+//! cargo +nightly fuzz run parse tests/corpus
+//! ```
+//!
+//! Add any crashing input that is found to `tests/corpus`.
+#![no_main]
+
+use json_partial::jsonish::{
+    self, coerce_scalars, to_json_string, to_jsonc_string_pretty, CoercionOptions,
+    NormalizeOptions, ParseOptions,
+};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|input: &str| {
+    for options in [
+        ParseOptions::default(),
+        ParseOptions::default()
+            .keep_comments(true)
+            .decode_nested_json(3)
+            .normalize_input(NormalizeOptions::default()),
+        ParseOptions::default().track_hypotheses(4),
+    ] {
+        if let Ok(value) = jsonish::parse(input, options) {
+            let _ = to_json_string(&value);
+            let _ = to_jsonc_string_pretty(&value);
+            let _ = value.comments();
+            let _ = coerce_scalars(&value, &CoercionOptions::default());
+        }
+    }
+});
@@ -0,0 +1,505 @@
+//! Lenient coercion of scalar leaves.
+//!
+//! LLMs frequently quote values that should have been numbers, booleans or
+//! nulls (`"1,234"`, `"$12.50"`, `"yes"`, `"N/A"`). The helpers in this module
+//! walk a parsed [`Value`] and rewrite such `Value::String` leaves, returning
+//! a [`Coercion`] record for every leaf they touched.
+
+use crate::jsonish::Value;
+
+/// Separators used when reading numbers written for humans.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NumberLocale {
+    pub decimal_separator: char,
+    pub group_separator: char,
+}
+
+impl NumberLocale {
+    /// `1,234.5`
+    pub const EN: NumberLocale = NumberLocale {
+        decimal_separator: '.',
+        group_separator: ',',
+    };
+    /// `1.234,5`
+    pub const DE: NumberLocale = NumberLocale {
+        decimal_separator: ',',
+        group_separator: '.',
+    };
+    /// `1 234,5` (regular, non-breaking and narrow non-breaking spaces)
+    pub const FR: NumberLocale = NumberLocale {
+        decimal_separator: ',',
+        group_separator: ' ',
+    };
+    /// `1'234.5`
+    pub const CH: NumberLocale = NumberLocale {
+        decimal_separator: '.',
+        group_separator: '\'',
+    };
+
+    fn is_group_separator(&self, c: char) -> bool {
+        c == self.group_separator
+            || (self.group_separator == ' ' && matches!(c, '\u{a0}' | '\u{202f}'))
+    }
+}
+
+impl Default for NumberLocale {
+    fn default() -> Self {
+        NumberLocale::EN
+    }
+}
+
+/// How `"45%"` is turned into a number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PercentMode {
+    /// `"45%"` becomes `45`.
+    #[default]
+    Keep,
+    /// `"45%"` becomes `0.45`.
+    Fraction,
+    /// Percentages are left as strings.
+    Disabled,
+}
+
+#[derive(Debug, Clone)]
+pub struct CoercionOptions {
+    pub locale: NumberLocale,
+    pub numbers: bool,
+    pub booleans: bool,
+    pub nulls: bool,
+    /// Strip currency symbols such as `$`, `€` or `£`.
+    pub currency: bool,
+    pub percent: PercentMode,
+    /// Expand `k`, `m`/`M`, `b`/`bn` suffixes (`"3.2k"` -> `3200`).
+    pub magnitude_suffixes: bool,
+    literals: Vec<(String, Value)>,
+}
+
+impl Default for CoercionOptions {
+    fn default() -> Self {
+        Self {
+            locale: NumberLocale::default(),
+            numbers: true,
+            booleans: true,
+            nulls: true,
+            currency: true,
+            percent: PercentMode::default(),
+            magnitude_suffixes: true,
+            literals: vec![],
+        }
+    }
+}
+
+impl CoercionOptions {
+    pub fn with_locale(mut self, locale: NumberLocale) -> Self {
+        self.locale = locale;
+        self
+    }
+
+    /// Registers a literal (matched case-insensitively after trimming) that
+    /// should always be replaced by `value`. Literals take precedence over the
+    /// built-in rules.
+    pub fn with_literal(mut self, literal: impl Into<String>, value: Value) -> Self {
+        self.literals
+            .push((literal.into().trim().to_lowercase(), value));
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoercionKind {
+    Number,
+    Boolean,
+    Null,
+    Literal,
+}
+
+/// A single rewritten leaf.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Coercion {
+    /// JSON pointer to the leaf, e.g. `/items/0/price`.
+    pub path: String,
+    pub original: String,
+    pub value: Value,
+    pub kind: CoercionKind,
+}
+
+/// Rewrites every coercible `Value::String` leaf of `value`.
+///
+/// Wrappers (`Markdown`, `FixedJson`, `AnyOf`, `Commented`) are preserved and
+/// their inner values coerced in place.
+pub fn coerce_scalars(value: &Value, options: &CoercionOptions) -> (Value, Vec<Coercion>) {
+    let mut coercions = vec![];
+    let value = coerce_value(value, options, &mut String::new(), &mut coercions);
+    (value, coercions)
+}
+
+fn coerce_value(
+    value: &Value,
+    options: &CoercionOptions,
+    path: &mut String,
+    coercions: &mut Vec<Coercion>,
+) -> Value {
+    match value {
+        Value::String(s) => match coerce_scalar(s, options) {
+            Some((coerced, kind)) => {
+                coercions.push(Coercion {
+                    path: path.clone(),
+                    original: s.clone(),
+                    value: coerced.clone(),
+                    kind,
+                });
+                coerced
+            }
+            None => value.clone(),
+        },
+        Value::Number(_) | Value::Boolean(_) | Value::Null => value.clone(),
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(k, v)| {
+                    let len = path.len();
+                    path.push('/');
+                    path.push_str(&k.replace('~', "~0").replace('/', "~1"));
+                    let v = coerce_value(v, options, path, coercions);
+                    path.truncate(len);
+                    (k.clone(), v)
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(
+            items
+                .iter()
+                .enumerate()
+                .map(|(i, v)| {
+                    let len = path.len();
+                    path.push_str(&format!("/{i}"));
+                    let v = coerce_value(v, options, path, coercions);
+                    path.truncate(len);
+                    v
+                })
+                .collect(),
+        ),
+        Value::Markdown(tag, inner) => Value::Markdown(
+            tag.clone(),
+            Box::new(coerce_value(inner, options, path, coercions)),
+        ),
+        Value::FixedJson(inner, fixes) => Value::FixedJson(
+            Box::new(coerce_value(inner, options, path, coercions)),
+            fixes.clone(),
+        ),
+        Value::AnyOf(items, original) => Value::AnyOf(
+            items
+                .iter()
+                .map(|v| coerce_value(v, options, path, coercions))
+                .collect(),
+            original.clone(),
+        ),
+        Value::Commented(inner, comments) => Value::Commented(
+            Box::new(coerce_value(inner, options, path, coercions)),
+            comments.clone(),
+        ),
+    }
+}
+
+/// Coerces a single string, returning `None` if no rule applies.
+pub fn coerce_scalar(s: &str, options: &CoercionOptions) -> Option<(Value, CoercionKind)> {
+    let trimmed = s.trim();
+    let lower = trimmed.to_lowercase();
+
+    if let Some((_, v)) = options.literals.iter().find(|(l, _)| *l == lower) {
+        return Some((v.clone(), CoercionKind::Literal));
+    }
+
+    if options.booleans {
+        match lower.as_str() {
+            "true" | "yes" | "y" | "on" => {
+                return Some((Value::Boolean(true), CoercionKind::Boolean))
+            }
+            "false" | "no" | "n" | "off" => {
+                return Some((Value::Boolean(false), CoercionKind::Boolean))
+            }
+            _ => {}
+        }
+    }
+
+    if options.nulls {
+        match lower.as_str() {
+            "null" | "none" | "nil" | "n/a" | "na" | "-" | "--" | "\u{2013}" | "\u{2014}" => {
+                return Some((Value::Null, CoercionKind::Null))
+            }
+            _ => {}
+        }
+    }
+
+    if options.numbers {
+        if let Some(n) = parse_number(trimmed, options) {
+            return Some((Value::Number(n), CoercionKind::Number));
+        }
+    }
+
+    None
+}
+
+const CURRENCY_SYMBOLS: &[char] = &['$', '€', '£', '¥', '₹', '₩', '₽', '₺', '₪', '₫', '¢'];
+
+fn parse_number(s: &str, options: &CoercionOptions) -> Option<serde_json::Number> {
+    let mut rest = s;
+
+    let mut negative = false;
+    if let Some(r) = rest
+        .strip_prefix('-')
+        .or_else(|| rest.strip_prefix('\u{2212}'))
+    {
+        negative = true;
+        rest = r.trim_start();
+    } else if let Some(r) = rest.strip_prefix('+') {
+        rest = r.trim_start();
+    }
+
+    if options.currency {
+        if let Some(r) = rest.strip_prefix(CURRENCY_SYMBOLS) {
+            rest = r.trim_start();
+        } else if let Some(r) = rest.strip_suffix(CURRENCY_SYMBOLS) {
+            rest = r.trim_end();
+        }
+        // `$-5`
+        if !negative {
+            if let Some(r) = rest.strip_prefix('-') {
+                negative = true;
+                rest = r.trim_start();
+            }
+        }
+    }
+
+    let mut percent = false;
+    if options.percent != PercentMode::Disabled {
+        if let Some(r) = rest.strip_suffix('%') {
+            percent = true;
+            rest = r.trim_end();
+        }
+    }
+
+    let mut multiplier = 1u64;
+    if options.magnitude_suffixes && !percent {
+        for (suffix, m) in [
+            ("bn", 1_000_000_000),
+            ("k", 1_000),
+            ("m", 1_000_000),
+            ("b", 1_000_000_000),
+        ] {
+            if let Some(r) = strip_suffix_ignore_ascii_case(rest, suffix) {
+                rest = r.trim_end();
+                multiplier = m;
+                break;
+            }
+        }
+    }
+
+    let digits = normalize_digits(rest, options.locale)?;
+
+    // Dividing keeps `57%` at 0.57, where multiplying by 0.01 does not
+    let divisor = match (percent, options.percent) {
+        (true, PercentMode::Fraction) => 100.0,
+        _ => 1.0,
+    };
+
+    if multiplier == 1 && divisor == 1.0 && !digits.contains('.') {
+        let signed = if negative {
+            format!("-{digits}")
+        } else {
+            digits.clone()
+        };
+        if let Ok(n) = signed.parse::<i64>() {
+            return Some(n.into());
+        }
+        if let Ok(n) = signed.parse::<u64>() {
+            return Some(n.into());
+        }
+    }
+
+    let mut n = digits.parse::<f64>().ok()? * multiplier as f64 / divisor;
+    if negative {
+        n = -n;
+    }
+    if n.fract() == 0.0 && n.abs() < (1u64 << 53) as f64 {
+        return Some((n as i64).into());
+    }
+    serde_json::Number::from_f64(n)
+}
+
+/// `s` without an ASCII `suffix` matched case-insensitively. Only ASCII is
+/// folded, so the byte offsets of `s` hold (`K`, the Kelvin sign, is not `k`).
+fn strip_suffix_ignore_ascii_case<'a>(s: &'a str, suffix: &str) -> Option<&'a str> {
+    let split = s.len().checked_sub(suffix.len())?;
+    if !s.is_char_boundary(split) || !s[split..].eq_ignore_ascii_case(suffix) {
+        return None;
+    }
+    Some(&s[..split])
+}
+
+/// Rewrites a locale formatted number into the `123456.78` form, validating
+/// that group separators only split the integer part into groups of three.
+fn normalize_digits(s: &str, locale: NumberLocale) -> Option<String> {
+    if s.is_empty() {
+        return None;
+    }
+
+    let (int_part, frac_part) = match s.rfind(locale.decimal_separator) {
+        Some(idx) => (
+            &s[..idx],
+            Some(&s[idx + locale.decimal_separator.len_utf8()..]),
+        ),
+        None => (s, None),
+    };
+
+    let groups = int_part
+        .split(|c| locale.is_group_separator(c))
+        .collect::<Vec<_>>();
+    if groups.len() > 1 {
+        let valid = !groups[0].is_empty()
+            && groups[0].len() <= 3
+            && groups[1..].iter().all(|g| g.len() == 3);
+        if !valid {
+            return None;
+        }
+    }
+
+    let mut out = String::with_capacity(s.len());
+    for g in &groups {
+        if !g.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        out.push_str(g);
+    }
+    if let Some(frac) = frac_part {
+        if frac.is_empty() || !frac.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        if out.is_empty() {
+            out.push('0');
+        }
+        out.push('.');
+        out.push_str(frac);
+    }
+
+    if out.is_empty() {
+        None
+    } else {
+        Some(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn num(s: &str, options: &CoercionOptions) -> Option<Value> {
+        coerce_scalar(s, options).map(|(v, _)| v)
+    }
+
+    #[test]
+    fn test_human_numbers() {
+        let opts = CoercionOptions::default();
+        assert_eq!(num("1,234", &opts), Some(Value::Number(1234.into())));
+        assert_eq!(
+            num("$12.50", &opts),
+            Some(Value::Number(serde_json::Number::from_f64(12.5).unwrap()))
+        );
+        assert_eq!(num("45%", &opts), Some(Value::Number(45.into())));
+        assert_eq!(num("3.2k", &opts), Some(Value::Number(3200.into())));
+        assert_eq!(
+            num("-1,000,000", &opts),
+            Some(Value::Number((-1_000_000).into()))
+        );
+        assert_eq!(num("1,23", &opts), None);
+        assert_eq!(num("2024-01-01", &opts), None);
+        assert_eq!(num("1.2.3", &opts), None);
+        assert_eq!(
+            num("2BN", &opts),
+            Some(Value::Number(2_000_000_000u64.into()))
+        );
+        // The Kelvin sign lowercases to `k` but is not a suffix
+        assert_eq!(num("5\u{212A}", &opts), None);
+    }
+
+    #[test]
+    fn test_locales() {
+        let de = CoercionOptions::default().with_locale(NumberLocale::DE);
+        assert_eq!(
+            num("1.234,5", &de),
+            Some(Value::Number(serde_json::Number::from_f64(1234.5).unwrap()))
+        );
+        let fr = CoercionOptions::default().with_locale(NumberLocale::FR);
+        assert_eq!(num("1\u{a0}234 €", &fr), Some(Value::Number(1234.into())));
+
+        let fraction = CoercionOptions {
+            percent: PercentMode::Fraction,
+            ..Default::default()
+        };
+        for (input, expected) in [("45%", 0.45), ("57%", 0.57), ("0.1%", 0.001)] {
+            assert_eq!(
+                num(input, &fraction),
+                Some(Value::Number(
+                    serde_json::Number::from_f64(expected).unwrap()
+                )),
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_booleans_nulls_and_literals() {
+        let opts = CoercionOptions::default()
+            .with_literal("unknown", Value::Null)
+            .with_literal("Yes please", Value::Boolean(true));
+        assert_eq!(num("Y", &opts), Some(Value::Boolean(true)));
+        assert_eq!(num(" No ", &opts), Some(Value::Boolean(false)));
+        assert_eq!(num("N/A", &opts), Some(Value::Null));
+        assert_eq!(num("-", &opts), Some(Value::Null));
+        assert_eq!(
+            coerce_scalar("UNKNOWN", &opts),
+            Some((Value::Null, CoercionKind::Literal))
+        );
+        assert_eq!(num("yes please", &opts), Some(Value::Boolean(true)));
+        assert_eq!(num("maybe", &opts), None);
+    }
+
+    #[test]
+    fn test_coerce_records_paths() {
+        let value = Value::AnyOf(
+            vec![Value::Object(vec![
+                ("price".to_string(), Value::String("$1,200".to_string())),
+                (
+                    "tags".to_string(),
+                    Value::Array(vec![
+                        Value::String("on".to_string()),
+                        Value::String("blue".to_string()),
+                    ]),
+                ),
+            ])],
+            String::new(),
+        );
+        let (coerced, coercions) = coerce_scalars(&value, &CoercionOptions::default());
+        assert_eq!(
+            coerced,
+            Value::AnyOf(
+                vec![Value::Object(vec![
+                    ("price".to_string(), Value::Number(1200.into())),
+                    (
+                        "tags".to_string(),
+                        Value::Array(vec![
+                            Value::Boolean(true),
+                            Value::String("blue".to_string()),
+                        ]),
+                    ),
+                ])],
+                String::new(),
+            )
+        );
+        assert_eq!(coercions.len(), 2);
+        assert_eq!(coercions[0].path, "/price");
+        assert_eq!(coercions[0].original, "$1,200");
+        assert_eq!(coercions[1].path, "/tags/0");
+        assert_eq!(coercions[1].kind, CoercionKind::Boolean);
+    }
+}
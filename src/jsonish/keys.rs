@@ -0,0 +1,445 @@
+//! Fuzzy correction of object keys against an expected key set.
+//!
+//! LLMs misspell (`naem`), re-case (`userName` vs `user_name`) or invent keys
+//! (`extra`, `yap`). [`correct_keys`] renames near misses to the expected
+//! names and drops or collects the keys that match nothing.
+
+use serde::de::{self, DeserializeOwned, Visitor};
+
+use crate::jsonish::{Fixes, Value};
+
+/// The keys an object is expected to have, optionally with the expected keys
+/// of nested objects.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExpectedKeys {
+    keys: Vec<String>,
+    nested: Vec<(String, ExpectedKeys)>,
+}
+
+impl ExpectedKeys {
+    pub fn new<S: Into<String>>(keys: impl IntoIterator<Item = S>) -> Self {
+        Self {
+            keys: keys.into_iter().map(Into::into).collect(),
+            nested: vec![],
+        }
+    }
+
+    /// Expected keys for the object (or array of objects) found under `key`.
+    pub fn with_nested(mut self, key: impl Into<String>, expected: ExpectedKeys) -> Self {
+        self.nested.push((key.into(), expected));
+        self
+    }
+
+    /// The serialized field names of a `serde` struct, honouring `rename`
+    /// and `rename_all` attributes.
+    ///
+    /// Only the top level is introspected; use [`ExpectedKeys::with_nested`]
+    /// for nested structs.
+    pub fn from_type<T: DeserializeOwned>() -> Self {
+        let mut fields: &'static [&'static str] = &[];
+        let _ = T::deserialize(FieldNames(&mut fields));
+        Self::new(fields.iter().copied())
+    }
+
+    /// The `properties` of a JSON schema, recursing into nested object and
+    /// array-of-object properties.
+    pub fn from_schema(schema: &serde_json::Value) -> Self {
+        let Some(properties) = schema.get("properties").and_then(|p| p.as_object()) else {
+            return Self::default();
+        };
+
+        let mut expected = Self::new(properties.keys().cloned());
+        for (key, property) in properties {
+            let nested = match property.get("items") {
+                Some(items) => Self::from_schema(items),
+                None => Self::from_schema(property),
+            };
+            if !nested.keys.is_empty() {
+                expected.nested.push((key.clone(), nested));
+            }
+        }
+        expected
+    }
+
+    pub fn keys(&self) -> &[String] {
+        &self.keys
+    }
+}
+
+/// What to do with keys that do not correspond to any expected key.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum UnknownKeys {
+    #[default]
+    Keep,
+    Drop,
+    /// Move them into an object stored under the given key.
+    Collect(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct KeyCorrectionOptions {
+    pub unknown_keys: UnknownKeys,
+    /// Largest edit distance (after case and separator normalisation) that
+    /// is still considered a misspelling.
+    pub max_distance: usize,
+}
+
+impl Default for KeyCorrectionOptions {
+    fn default() -> Self {
+        Self {
+            unknown_keys: UnknownKeys::Keep,
+            max_distance: 2,
+        }
+    }
+}
+
+/// Renames near-miss keys of every object candidate in `value` to the keys in
+/// `expected`.
+///
+/// Wrappers (`Markdown`, `FixedJson`, `AnyOf`, `Commented`) are preserved.
+/// Every rename, drop or collection is reported in the returned fixes.
+pub fn correct_keys(
+    value: &Value,
+    expected: &ExpectedKeys,
+    options: &KeyCorrectionOptions,
+) -> (Value, Vec<Fixes>) {
+    let mut fixes = vec![];
+    let value = correct_value(value, expected, options, &mut fixes);
+    (value, fixes)
+}
+
+fn correct_value(
+    value: &Value,
+    expected: &ExpectedKeys,
+    options: &KeyCorrectionOptions,
+    fixes: &mut Vec<Fixes>,
+) -> Value {
+    match value {
+        Value::Object(fields) => Value::Object(correct_object(fields, expected, options, fixes)),
+        Value::Array(items) => Value::Array(
+            items
+                .iter()
+                .map(|v| correct_value(v, expected, options, fixes))
+                .collect(),
+        ),
+        Value::Markdown(tag, inner) => Value::Markdown(
+            tag.clone(),
+            Box::new(correct_value(inner, expected, options, fixes)),
+        ),
+        Value::FixedJson(inner, inner_fixes) => Value::FixedJson(
+            Box::new(correct_value(inner, expected, options, fixes)),
+            inner_fixes.clone(),
+        ),
+        Value::AnyOf(items, original) => Value::AnyOf(
+            items
+                .iter()
+                .map(|v| correct_value(v, expected, options, fixes))
+                .collect(),
+            original.clone(),
+        ),
+        Value::Commented(inner, comments) => Value::Commented(
+            Box::new(correct_value(inner, expected, options, fixes)),
+            comments.clone(),
+        ),
+        Value::String(_) | Value::Number(_) | Value::Boolean(_) | Value::Null => value.clone(),
+    }
+}
+
+fn correct_object(
+    fields: &[(String, Value)],
+    expected: &ExpectedKeys,
+    options: &KeyCorrectionOptions,
+    fixes: &mut Vec<Fixes>,
+) -> Vec<(String, Value)> {
+    if expected.keys.is_empty() {
+        return fields.to_vec();
+    }
+
+    // Expected keys that are already spelled correctly can't be claimed by a
+    // misspelling.
+    let mut taken = expected
+        .keys
+        .iter()
+        .map(|k| fields.iter().any(|(f, _)| f == k))
+        .collect::<Vec<_>>();
+
+    let mut result = Vec::with_capacity(fields.len());
+    let mut unknown = vec![];
+    for (key, value) in fields {
+        let target = if expected.keys.contains(key) {
+            Some(key.clone())
+        } else {
+            match closest_key(key, &expected.keys, &taken, options.max_distance) {
+                Some(idx) => {
+                    taken[idx] = true;
+                    let to = expected.keys[idx].clone();
+                    fixes.push(Fixes::RenamedKey {
+                        from: key.clone(),
+                        to: to.clone(),
+                    });
+                    Some(to)
+                }
+                None => None,
+            }
+        };
+
+        match target {
+            Some(key) => {
+                let value = match expected.nested.iter().find(|(k, _)| *k == key) {
+                    Some((_, nested)) => correct_value(value, nested, options, fixes),
+                    None => value.clone(),
+                };
+                result.push((key, value));
+            }
+            None => match &options.unknown_keys {
+                UnknownKeys::Keep => result.push((key.clone(), value.clone())),
+                UnknownKeys::Drop => fixes.push(Fixes::DroppedKey(key.clone())),
+                UnknownKeys::Collect(into) => {
+                    fixes.push(Fixes::CollectedKey {
+                        key: key.clone(),
+                        into: into.clone(),
+                    });
+                    unknown.push((key.clone(), value.clone()));
+                }
+            },
+        }
+    }
+
+    if let UnknownKeys::Collect(into) = &options.unknown_keys {
+        if !unknown.is_empty() {
+            result.push((into.clone(), Value::Object(unknown)));
+        }
+    }
+
+    result
+}
+
+/// Finds the unclaimed expected key closest to `key`. Ties are treated as
+/// ambiguous and produce no match.
+fn closest_key(
+    key: &str,
+    expected: &[String],
+    taken: &[bool],
+    max_distance: usize,
+) -> Option<usize> {
+    let normalized = normalize(key);
+    if normalized.is_empty() {
+        return None;
+    }
+    // Short keys tolerate fewer edits; one or two letter keys must match
+    // exactly (`id` must not become `at`).
+    let limit = max_distance.min(normalized.chars().count() / 3);
+
+    let mut best: Option<(usize, usize)> = None;
+    let mut tied = false;
+    for (idx, candidate) in expected.iter().enumerate() {
+        if taken[idx] {
+            continue;
+        }
+        let distance = edit_distance(&normalized, &normalize(candidate));
+        if distance > limit {
+            continue;
+        }
+        match best {
+            Some((_, d)) if d < distance => {}
+            Some((_, d)) if d == distance => tied = true,
+            _ => {
+                best = Some((idx, distance));
+                tied = false;
+            }
+        }
+    }
+
+    match best {
+        Some((idx, _)) if !tied => Some(idx),
+        _ => None,
+    }
+}
+
+/// Folds case and drops snake/kebab/space separators so that `userName`,
+/// `user_name`, `user-name` and `User Name` compare equal.
+fn normalize(key: &str) -> String {
+    key.chars()
+        .filter(|c| !matches!(c, '_' | '-' | ' ' | '.'))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Optimal string alignment distance (Levenshtein with adjacent
+/// transpositions), so `naem` is one edit away from `name`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a = a.chars().collect::<Vec<_>>();
+    let b = b.chars().collect::<Vec<_>>();
+    let mut d = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+/// A deserializer that records the field names passed to
+/// `deserialize_struct` and then bails out.
+struct FieldNames<'a>(&'a mut &'static [&'static str]);
+
+#[derive(Debug)]
+struct Introspected;
+
+impl std::fmt::Display for Introspected {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "introspected")
+    }
+}
+
+impl std::error::Error for Introspected {}
+
+impl de::Error for Introspected {
+    fn custom<T: std::fmt::Display>(_msg: T) -> Self {
+        Introspected
+    }
+}
+
+impl<'de> de::Deserializer<'de> for FieldNames<'_> {
+    type Error = Introspected;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+        Err(Introspected)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        *self.0 = fields;
+        Err(Introspected)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map enum identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jsonish::{jsonish_to_serde, parse, ParseOptions};
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Person {
+        name: String,
+        age: u8,
+    }
+
+    #[test]
+    fn test_misspelled_keys_from_type() {
+        let input = r#"{naem:"Alice", age:30, extra:"remove me", yap:"   noisy message   "}"#;
+        let value = parse(input, ParseOptions::default()).unwrap();
+        let options = KeyCorrectionOptions {
+            unknown_keys: UnknownKeys::Drop,
+            ..Default::default()
+        };
+        let (value, fixes) = correct_keys(&value, &ExpectedKeys::from_type::<Person>(), &options);
+
+        let person: Person = serde_json::from_value(jsonish_to_serde(&value)).unwrap();
+        assert_eq!(
+            person,
+            Person {
+                name: "Alice".to_string(),
+                age: 30
+            }
+        );
+        assert_eq!(
+            fixes,
+            vec![
+                Fixes::RenamedKey {
+                    from: "naem".to_string(),
+                    to: "name".to_string()
+                },
+                Fixes::DroppedKey("extra".to_string()),
+                Fixes::DroppedKey("yap".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_case_styles_and_collect() {
+        let value = Value::Object(vec![
+            ("UserName".to_string(), Value::String("bob".to_string())),
+            ("created-at".to_string(), Value::Number(1.into())),
+            ("id".to_string(), Value::Number(2.into())),
+            ("at".to_string(), Value::Null),
+        ]);
+        let expected = ExpectedKeys::new(["user_name", "createdAt", "id"]);
+        let options = KeyCorrectionOptions {
+            unknown_keys: UnknownKeys::Collect("_extra".to_string()),
+            ..Default::default()
+        };
+        let (value, fixes) = correct_keys(&value, &expected, &options);
+        assert_eq!(
+            value,
+            Value::Object(vec![
+                ("user_name".to_string(), Value::String("bob".to_string())),
+                ("createdAt".to_string(), Value::Number(1.into())),
+                ("id".to_string(), Value::Number(2.into())),
+                (
+                    "_extra".to_string(),
+                    Value::Object(vec![("at".to_string(), Value::Null)])
+                ),
+            ])
+        );
+        assert_eq!(fixes.len(), 3);
+    }
+
+    #[test]
+    fn test_nested_schema() {
+        let schema = serde_json::json!({
+            "type": "object",
+            "properties": {
+                "users": {
+                    "type": "array",
+                    "items": { "properties": { "email": {}, "name": {} } }
+                }
+            }
+        });
+        let value = Value::Object(vec![(
+            "Users".to_string(),
+            Value::Array(vec![Value::Object(vec![(
+                "emial".to_string(),
+                Value::String("a@b.c".to_string()),
+            )])]),
+        )]);
+        let (value, _) = correct_keys(
+            &value,
+            &ExpectedKeys::from_schema(&schema),
+            &KeyCorrectionOptions::default(),
+        );
+        assert_eq!(
+            value,
+            Value::Object(vec![(
+                "users".to_string(),
+                Value::Array(vec![Value::Object(vec![(
+                    "email".to_string(),
+                    Value::String("a@b.c".to_string()),
+                )])]),
+            )])
+        );
+    }
+}
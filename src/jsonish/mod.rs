@@ -8,11 +8,41 @@ pub use value::{Fixes, Value};
 
 // pub use iterative_parser::{parse_jsonish_value, JSONishOptions};
 mod parser;
-pub use parser::{parse, ParseOptions};
+pub use parser::{
+    normalize, parse, parse_many, parse_many_with, split_reasoning, Document, HolePolicy, ManyMode,
+    Normalization, NormalizationKind, NormalizeOptions, NormalizedInput, ParseOptions,
+    ReasoningBlock, ReasoningMode, ReasoningOptions, ReasoningSplit,
+};
 
 mod to_serde;
 pub use to_serde::jsonish_to_serde;
 
+mod to_jsonc;
+pub use to_jsonc::to_jsonc_string_pretty;
+
+mod coerce;
+pub use coerce::{
+    coerce_scalar, coerce_scalars, Coercion, CoercionKind, CoercionOptions, NumberLocale,
+    PercentMode,
+};
+
+mod keys;
+pub use keys::{correct_keys, ExpectedKeys, KeyCorrectionOptions, UnknownKeys};
+
+mod provider;
+pub use provider::{parse_response, read_response, Provider, ProviderResponse};
+
+mod repair;
+pub use repair::{
+    apply_edits, json_region, parse_with_edits, repair_source, replace_json, EditKind, TextEdit,
+};
+
+mod stream;
+pub use stream::StreamDecoder;
+
+mod tool_calls;
+pub use tool_calls::{extract_tool_calls, ToolCall, ToolCallFormat};
+
 /// Converts a `jsonish::Value` into a compact JSON string.
 ///
 /// Returns an error if serialization via `serde_json` fails.
@@ -32,7 +62,7 @@ pub fn to_json_string_pretty(value: &Value) -> Result<String, serde_json::Error>
 #[cfg(test)]
 mod tests {
     // Bring the conversion functions and jsonish module into scope.
-    use super::{parse, to_json_string, ParseOptions};
+    use super::{jsonish_to_serde, parse, to_json_string, to_jsonc_string_pretty, ParseOptions};
 
     /// Test that `to_json_string` converts a malformed JSON (with missing comma,
     /// misnamed key, superfluous key, and extra whitespace) into a compact JSON string.
@@ -55,4 +85,54 @@ mod tests {
         .unwrap();
         assert_eq!(output_value, expected_value);
     }
+
+    #[test]
+    fn test_keep_comments() {
+        let input = r#"{
+            // how well the answer did
+            "score": 7 // because the answer lacked sources
+            , "tags": ["a", /* primary */ "b"],
+            "url": https://example.com/a
+        }"#;
+
+        let value = parse(input, ParseOptions::default().keep_comments(true)).unwrap();
+        assert_eq!(
+            value.comments(),
+            vec![
+                ("/score".to_string(), "how well the answer did".to_string()),
+                (
+                    "/score".to_string(),
+                    "because the answer lacked sources".to_string()
+                ),
+                ("/tags/1".to_string(), "primary".to_string()),
+            ]
+        );
+        assert_eq!(
+            to_jsonc_string_pretty(&value).unwrap(),
+            r#"{
+  // how well the answer did
+  // because the answer lacked sources
+  "score": 7,
+  "tags": [
+    "a",
+    // primary
+    "b"
+  ],
+  "url": "https://example.com/a"
+}"#
+        );
+
+        // By default comments are dropped and an unquoted value reads on
+        // through a `//`, as before.
+        let value = parse(input, ParseOptions::default()).unwrap();
+        assert!(value.comments().is_empty());
+        assert_eq!(
+            jsonish_to_serde(&value),
+            serde_json::json!({
+                "score": "7 // because the answer lacked sources",
+                "tags": ["a", "b"],
+                "url": "https://example.com/a"
+            })
+        );
+    }
 }
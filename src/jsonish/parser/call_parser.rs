@@ -0,0 +1,314 @@
+use crate::jsonish::Value;
+
+use super::{entry, ParseOptions, ParsingMode};
+
+/// Finds tool calls written as code and reads each into
+/// `{"name": ..., "arguments": {...}}`.
+///
+/// ```text
+/// search(query="rust json", limit=5)
+/// Action: get_weather(city='Paris')
+/// ```
+///
+/// A call must start a line (after an optional `Label:`) and end it, so that
+/// a `word(s)` inside a sentence is left alone. Keyword arguments go into
+/// `arguments`; positional ones are kept in order under `positional`.
+/// Arguments are Python or JSON literals: lists and dicts go through the
+/// fixing parser, nested calls become call objects, and bare words are
+/// strings.
+pub fn parse(str: &str, options: &ParseOptions) -> Vec<Value> {
+    let mut calls = vec![];
+    let mut offset = 0;
+    while offset < str.len() {
+        let line_end = str[offset..]
+            .find('\n')
+            .map_or(str.len(), |idx| offset + idx + 1);
+        match call_at(&str[offset..], options) {
+            Some((call, len)) => {
+                calls.push(call);
+                // Continue after the line the call ends on
+                offset += len;
+                offset = str[offset..]
+                    .find('\n')
+                    .map_or(str.len(), |idx| offset + idx + 1);
+            }
+            None => offset = line_end,
+        }
+    }
+    calls
+}
+
+/// A call at the start of `str` and the length of the text it spans.
+fn call_at(str: &str, options: &ParseOptions) -> Option<(Value, usize)> {
+    let line = str.trim_start_matches([' ', '\t']);
+    let line = strip_label(line).trim_start_matches('`');
+    let start = str.len() - line.len();
+
+    let (call, len) = call(line, options)?;
+    let rest = &line[len..];
+    let trailing = rest.find('\n').map_or(rest, |idx| &rest[..idx]);
+    trailing
+        .trim_matches(|c: char| c.is_whitespace() || c == ';' || c == '`')
+        .is_empty()
+        .then_some((call, start + len))
+}
+
+/// Removes a leading `Action:` / `Tool call:` label.
+fn strip_label(line: &str) -> &str {
+    match line.split_once(':') {
+        Some((label, rest))
+            if !label.is_empty()
+                && label.len() <= 30
+                && label
+                    .chars()
+                    .all(|c| c.is_alphabetic() || c == ' ' || c == '*') =>
+        {
+            rest.trim_start()
+        }
+        _ => line,
+    }
+}
+
+/// Parses `name(args)` at the start of `str`.
+fn call(str: &str, options: &ParseOptions) -> Option<(Value, usize)> {
+    let name_len = str
+        .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.'))
+        .unwrap_or(str.len());
+    let name = &str[..name_len];
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit() || c == '.') {
+        return None;
+    }
+    let args_len = enclosed_len(&str[name_len..])?;
+    let args = &str[name_len + 1..name_len + args_len - 1];
+
+    let mut positional = vec![];
+    let mut arguments = vec![];
+    for arg in split_top_level(args, ',') {
+        let arg = arg.trim();
+        if arg.is_empty() {
+            continue;
+        }
+        match keyword(arg) {
+            Some((key, value)) => arguments.push((key.to_string(), literal(value, options))),
+            None => positional.push(literal(arg, options)),
+        }
+    }
+
+    let mut fields = vec![
+        ("name".to_string(), Value::String(name.to_string())),
+        ("arguments".to_string(), Value::Object(arguments)),
+    ];
+    if !positional.is_empty() {
+        fields.push(("positional".to_string(), Value::Array(positional)));
+    }
+    Some((Value::Object(fields), name_len + args_len))
+}
+
+/// Splits `key=value`.
+fn keyword(arg: &str) -> Option<(&str, &str)> {
+    let key_len = arg.find(|c: char| !(c.is_alphanumeric() || c == '_'))?;
+    let key = &arg[..key_len];
+    let value = arg[key_len..].trim_start().strip_prefix('=')?;
+    if key.is_empty() || key.starts_with(|c: char| c.is_ascii_digit()) || value.starts_with('=') {
+        return None;
+    }
+    Some((key, value.trim()))
+}
+
+fn literal(text: &str, options: &ParseOptions) -> Value {
+    match text {
+        "True" | "true" => return Value::Boolean(true),
+        "False" | "false" => return Value::Boolean(false),
+        "None" | "null" | "nil" => return Value::Null,
+        _ => {}
+    }
+    if let Ok(n) = text.parse::<serde_json::Number>() {
+        return Value::Number(n);
+    }
+    if text.starts_with('"') {
+        if let Ok(s) = serde_json::from_str::<String>(text) {
+            return Value::String(s);
+        }
+    }
+    if let Some(inner) = text.strip_prefix('\'').and_then(|t| t.strip_suffix('\'')) {
+        return Value::String(inner.replace("\\'", "'").replace("\\\\", "\\"));
+    }
+    // Nested calls share the depth limit of nested parses
+    let options = &ParseOptions {
+        depth: options.depth + 1,
+        ..*options
+    };
+    if options.depth > 100 {
+        return Value::String(text.to_string());
+    }
+    if let Some((call, len)) = call(text, options) {
+        if len == text.len() {
+            return call;
+        }
+    }
+    if text.starts_with(['[', '{', '(']) {
+        let options = options.next_from_mode(ParsingMode::JsonMarkdown);
+        // Tuples are lists
+        let text = match text.strip_prefix('(').and_then(|t| t.strip_suffix(')')) {
+            Some(inner) => format!("[{inner}]"),
+            None => text.to_string(),
+        };
+        if let Ok(value) = entry::parse(&json_constants(&text), options) {
+            return value;
+        }
+    }
+    Value::String(text.to_string())
+}
+
+/// The length of the bracketed text at the start of `str`, brackets included.
+fn enclosed_len(str: &str) -> Option<usize> {
+    if !str.starts_with(['(', '[', '{']) {
+        return None;
+    }
+    let mut depth = 0;
+    let mut quote = None;
+    let mut escaped = false;
+    for (idx, c) in str.char_indices() {
+        match (quote, c) {
+            _ if escaped => escaped = false,
+            (Some(_), '\\') => escaped = true,
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '(' | '[' | '{') => depth += 1,
+            (None, ')' | ']' | '}') => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(idx + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Splits `str` at each `separator` outside brackets and quotes.
+fn split_top_level(str: &str, separator: char) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut start = 0;
+    let mut depth = 0usize;
+    let mut quote = None;
+    let mut escaped = false;
+    for (idx, c) in str.char_indices() {
+        match (quote, c) {
+            _ if escaped => escaped = false,
+            (Some(_), '\\') => escaped = true,
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '(' | '[' | '{') => depth += 1,
+            (None, ')' | ']' | '}') => depth = depth.saturating_sub(1),
+            (None, c) if c == separator && depth == 0 => {
+                parts.push(&str[start..idx]);
+                start = idx + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&str[start..]);
+    parts
+}
+
+/// Rewrites Python's `True`, `False` and `None` outside strings as JSON.
+fn json_constants(str: &str) -> String {
+    let mut out = String::with_capacity(str.len());
+    let mut quote = None;
+    let mut escaped = false;
+    let mut word = String::new();
+    let flush = |word: &mut String, out: &mut String| {
+        out.push_str(match word.as_str() {
+            "True" => "true",
+            "False" => "false",
+            "None" => "null",
+            word => word,
+        });
+        word.clear();
+    };
+    for c in str.chars() {
+        if quote.is_none() && (c.is_alphanumeric() || c == '_') {
+            word.push(c);
+            continue;
+        }
+        flush(&mut word, &mut out);
+        match (quote, c) {
+            _ if escaped => escaped = false,
+            (Some(_), '\\') => escaped = true,
+            (Some(q), c) if c == q => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            _ => {}
+        }
+        out.push(c);
+    }
+    flush(&mut word, &mut out);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jsonish::jsonish_to_serde;
+
+    fn calls(str: &str) -> Vec<serde_json::Value> {
+        parse(str, &ParseOptions::default())
+            .iter()
+            .map(jsonish_to_serde)
+            .collect()
+    }
+
+    #[test]
+    fn test_keyword_and_positional_arguments() {
+        assert_eq!(
+            calls("search(query=\"rust json\", limit=5)"),
+            vec![serde_json::json!({
+                "name": "search",
+                "arguments": {"query": "rust json", "limit": 5},
+            })]
+        );
+        assert_eq!(
+            calls(
+                "Thought: I need the weather.\nAction: get_weather(city='Paris', units = None);\n"
+            ),
+            vec![serde_json::json!({
+                "name": "get_weather",
+                "arguments": {"city": "Paris", "units": null},
+            })]
+        );
+        assert_eq!(
+            calls("`math.add(1, 2.5, strict=True)`"),
+            vec![serde_json::json!({
+                "name": "math.add",
+                "arguments": {"strict": true},
+                "positional": [1, 2.5],
+            })]
+        );
+    }
+
+    #[test]
+    fn test_nested_literals_and_calls() {
+        assert_eq!(
+            calls("book(\n    guests=[{'name': 'Ann', 'vip': True}, {name: Bo}],\n    dates=('mon', 'tue'),\n    notify=email(to=\"a@b.c, d\"),\n)"),
+            vec![serde_json::json!({
+                "name": "book",
+                "arguments": {
+                    "guests": [{"name": "Ann", "vip": true}, {"name": "Bo"}],
+                    "dates": ["mon", "tue"],
+                    "notify": {"name": "email", "arguments": {"to": "a@b.c, d"}},
+                },
+            })]
+        );
+    }
+
+    #[test]
+    fn test_calls_in_prose_are_ignored() {
+        assert!(calls("The function(s) listed below are deprecated.").is_empty());
+        assert!(calls("Call print(x) to see it.").is_empty());
+        assert!(calls("open(").is_empty());
+        assert_eq!(calls("a()\nb(1)").len(), 2);
+    }
+}
@@ -0,0 +1,186 @@
+use crate::jsonish::Value;
+
+use super::{markdown_table, ParseOptions};
+
+/// Delimiters tried, in order, when a block does not say what it holds.
+const DELIMITERS: [char; 3] = ['\t', ',', ';'];
+
+struct Field {
+    text: String,
+    quoted: bool,
+}
+
+/// The delimiter of `str` if it reads as CSV / TSV: at least two records,
+/// all with the same number of fields (two or more).
+pub fn sniff(str: &str) -> Option<char> {
+    DELIMITERS.into_iter().find(|&delimiter| {
+        let records = records(str, delimiter);
+        records.len() >= 2
+            && records[0].len() >= 2
+            && records.iter().all(|r| r.len() == records[0].len())
+    })
+}
+
+/// Reads delimited records into an array with one object per record.
+///
+/// The first record is the header when all its fields are distinct,
+/// non-empty text; otherwise keys are `column1`, `column2`, ... and every
+/// record is data. Quoted fields (`"a, b"`, `"say ""hi"""`) may span lines and
+/// are kept as strings; other fields are typed like table cells.
+pub fn parse(str: &str, delimiter: char, options: &ParseOptions) -> Value {
+    let mut records = records(str, delimiter).into_iter().peekable();
+
+    let is_header = records.peek().is_some_and(|first| {
+        first.iter().enumerate().all(|(idx, field)| {
+            !field.text.is_empty()
+                && (field.quoted
+                    || matches!(
+                        markdown_table::scalar(&field.text, options),
+                        Value::String(_)
+                    ))
+                && first[..idx].iter().all(|other| other.text != field.text)
+        })
+    });
+    let header = match records.peek() {
+        Some(first) if is_header => {
+            let header = first.iter().map(|f| f.text.clone()).collect::<Vec<_>>();
+            records.next();
+            header
+        }
+        Some(first) => (1..=first.len()).map(|n| format!("column{n}")).collect(),
+        None => vec![],
+    };
+
+    Value::Array(
+        records
+            .map(|record| {
+                let mut fields = record.into_iter();
+                Value::Object(
+                    header
+                        .iter()
+                        .map(|key| {
+                            let value = match fields.next() {
+                                Some(f) if f.quoted => Value::String(f.text),
+                                Some(f) => markdown_table::scalar(&f.text, options),
+                                None => Value::Null,
+                            };
+                            (key.clone(), value)
+                        })
+                        .collect(),
+                )
+            })
+            .collect(),
+    )
+}
+
+/// Splits `str` into records of fields, skipping blank lines.
+fn records(str: &str, delimiter: char) -> Vec<Vec<Field>> {
+    let mut records = vec![];
+    let mut record = vec![];
+    let mut field = Field {
+        text: String::new(),
+        quoted: false,
+    };
+    let mut in_quotes = false;
+
+    let mut chars = str.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes => {
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    field.text.push('"');
+                } else {
+                    in_quotes = false;
+                }
+            }
+            '"' if field.text.trim().is_empty() && !field.quoted => {
+                field.text.clear();
+                field.quoted = true;
+                in_quotes = true;
+            }
+            c if in_quotes => field.text.push(c),
+            c if c == delimiter => record.push(finish(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                if !record.is_empty() || field.quoted || !field.text.trim().is_empty() {
+                    record.push(finish(&mut field));
+                    records.push(std::mem::take(&mut record));
+                }
+                field.text.clear();
+            }
+            // Padding after a closing quote
+            c if field.quoted && c.is_whitespace() => {}
+            c => field.text.push(c),
+        }
+    }
+    if !record.is_empty() || field.quoted || !field.text.trim().is_empty() {
+        record.push(finish(&mut field));
+        records.push(record);
+    }
+    records
+}
+
+fn finish(field: &mut Field) -> Field {
+    let field = std::mem::replace(
+        field,
+        Field {
+            text: String::new(),
+            quoted: false,
+        },
+    );
+    Field {
+        text: if field.quoted {
+            field.text
+        } else {
+            field.text.trim().to_string()
+        },
+        quoted: field.quoted,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jsonish::jsonish_to_serde;
+
+    fn csv(str: &str) -> serde_json::Value {
+        let delimiter = sniff(str).unwrap();
+        jsonish_to_serde(&parse(str, delimiter, &ParseOptions::default()))
+    }
+
+    #[test]
+    fn test_header_and_quoted_fields() {
+        let input =
+            "name, age ,note\r\n\"Smith, J\",42,\"said \"\"hi\"\"\nthen left\"\n\"007\",,true\n";
+        assert_eq!(sniff(input), Some(','));
+        assert_eq!(
+            csv(input),
+            serde_json::json!([
+                {"name": "Smith, J", "age": 42, "note": "said \"hi\"\nthen left"},
+                {"name": "007", "age": null, "note": true},
+            ])
+        );
+    }
+
+    #[test]
+    fn test_inferred_header() {
+        assert_eq!(
+            csv("1\tAlice\n2\tBob"),
+            serde_json::json!([
+                {"column1": 1, "column2": "Alice"},
+                {"column1": 2, "column2": "Bob"},
+            ])
+        );
+        assert_eq!(
+            csv("a;a\n1;2"),
+            serde_json::json!([{"column1": "a", "column2": "a"}, {"column1": 1, "column2": 2}])
+        );
+    }
+
+    #[test]
+    fn test_sniff_rejects_ragged_text() {
+        assert_eq!(sniff("Hello, world.\nThis is prose"), None);
+        assert_eq!(sniff("a,b"), None);
+    }
+}
@@ -2,9 +2,11 @@ use anyhow::Result;
 
 use crate::jsonish::{
     parser::{
-        fixing_parser,
+        call_parser, fixing_parser,
         markdown_parser::{self, MarkdownResult},
-        multi_json_parser,
+        markdown_table, multi_json_parser, nested_json, normalize, prose_parser,
+        reasoning::{self, ReasoningMode, ReasoningSplit},
+        yaml_parser,
     },
     value::Fixes,
     Value,
@@ -12,7 +14,155 @@ use crate::jsonish::{
 
 use super::ParseOptions;
 
-pub fn parse(str: &str, mut options: ParseOptions) -> Result<Value> {
+pub fn parse(str: &str, options: ParseOptions) -> Result<Value> {
+    if options.depth > 0 {
+        return parse_func(str, options);
+    }
+
+    if let Some(reasoning) = options.reasoning {
+        let split = reasoning::split_reasoning(str, &reasoning);
+        if !split.reasoning.is_empty() {
+            let options = ParseOptions {
+                reasoning: None,
+                ..options
+            };
+            return parse_answer(str, split, reasoning.mode, options);
+        }
+    }
+
+    let normalized = options
+        .normalization
+        .map(|n| normalize::normalize(str, &n))
+        .filter(|n| !n.edits.is_empty());
+
+    let mut value = match normalized {
+        Some(normalized) => match parse_func(&normalized.text, options)? {
+            // Nothing structured was found, hand back the input untouched.
+            Value::String(_) => Value::String(str.to_string()),
+            value => attach_fixes(
+                value,
+                str,
+                normalized
+                    .kinds()
+                    .into_iter()
+                    .map(Fixes::NormalizedInput)
+                    .collect(),
+            ),
+        },
+        None => parse_func(str, options)?,
+    };
+
+    if options.nested_json_depth > 0 {
+        value = nested_json::decode(value, &options, options.nested_json_depth);
+    }
+    Ok(value)
+}
+
+/// Parses the answer left by the reasoning pre-pass. With
+/// `ReasoningMode::Isolate`, values found in the reasoning follow the
+/// answer's candidates.
+fn parse_answer(
+    str: &str,
+    split: ReasoningSplit,
+    mode: ReasoningMode,
+    options: ParseOptions,
+) -> Result<Value> {
+    let mut candidates = match parse(&split.answer, options) {
+        Ok(Value::AnyOf(items, _)) => items,
+        Ok(value) => vec![value],
+        Err(e) if mode == ReasoningMode::Isolate => {
+            log::debug!("Failed to parse the answer: {:?}", e);
+            vec![]
+        }
+        Err(e) => return Err(e),
+    };
+
+    if mode == ReasoningMode::Isolate {
+        let options = ParseOptions {
+            allow_as_string: false,
+            ..options
+        };
+        for block in &split.reasoning {
+            match parse(&block.text, options) {
+                Ok(Value::AnyOf(items, _)) => candidates.extend(
+                    items
+                        .into_iter()
+                        .map(|v| attach_fixes(v, &block.text, vec![Fixes::FoundInReasoning])),
+                ),
+                Ok(value) => candidates.push(attach_fixes(
+                    value,
+                    &block.text,
+                    vec![Fixes::FoundInReasoning],
+                )),
+                Err(e) => log::debug!("No value in reasoning: {:?}", e),
+            }
+        }
+    }
+    if candidates.is_empty() {
+        return Err(anyhow::anyhow!("Failed to parse JSON"));
+    }
+
+    let fixes = split
+        .reasoning
+        .into_iter()
+        .map(|block| Fixes::StrippedReasoning(block.text))
+        .collect();
+    Ok(attach_fixes(
+        Value::AnyOf(candidates, str.to_string()),
+        str,
+        fixes,
+    ))
+}
+
+/// Records `fixes` on every candidate of a top-level result.
+fn attach_fixes(value: Value, original: &str, fixes: Vec<Fixes>) -> Value {
+    let attach = |v: Value| match v {
+        Value::FixedJson(inner, mut existing) => {
+            existing.extend(fixes.iter().cloned());
+            Value::FixedJson(inner, existing)
+        }
+        v => Value::FixedJson(Box::new(v), fixes.clone()),
+    };
+
+    match value {
+        Value::AnyOf(items, _) => Value::AnyOf(
+            items.into_iter().map(attach).collect(),
+            original.to_string(),
+        ),
+        v => attach(v),
+    }
+}
+
+/// The candidates for one or more values found by a stage: each value and,
+/// when there are several, all of them as a list.
+fn found(items: Vec<Value>, fix: Fixes, original: &str) -> Value {
+    let items = match items.len() {
+        1 => items,
+        _ => {
+            let all = Value::Array(items.clone());
+            items.into_iter().chain(std::iter::once(all)).collect()
+        }
+    };
+    Value::AnyOf(
+        items
+            .into_iter()
+            .map(|v| Value::FixedJson(v.into(), vec![fix.clone()]))
+            .collect(),
+        original.to_string(),
+    )
+}
+
+/// Whether `value` is a code block that was read as a table.
+fn is_converted_table(value: &Value) -> bool {
+    match value {
+        Value::Markdown(_, inner) => is_converted_table(inner),
+        Value::AnyOf(items, _) => items.first().is_some_and(is_converted_table),
+        Value::FixedJson(_, fixes) => fixes.contains(&Fixes::ConvertedTable),
+        _ => false,
+    }
+}
+
+fn parse_func(str: &str, mut options: ParseOptions) -> Result<Value> {
     log::debug!("Parsing:\n{:?}\n-------\n{}\n-------", options, str);
 
     options.depth += 1;
@@ -62,7 +212,7 @@ pub fn parse(str: &str, mut options: ParseOptions) -> Result<Value> {
                             MarkdownResult::String(s) => Some(Value::String(s.to_string())),
                             _ => None,
                         })
-                        .map(|v| {
+                        .map(|_v| {
                             parse(
                                 str,
                                 options.next_from_mode(
@@ -88,11 +238,25 @@ pub fn parse(str: &str, mut options: ParseOptions) -> Result<Value> {
                         .map(|(s, v)| Value::Markdown(s.to_string(), Box::new(v)))
                         .collect::<Vec<_>>();
                     let array = Value::Array(items.clone());
-                    let items = items
-                        .into_iter()
-                        .chain(std::iter::once(array))
-                        .chain(others)
-                        .collect::<Vec<_>>();
+                    // Tables read from code blocks, and the list holding
+                    // them, follow the JSON in the text around them, as
+                    // tables in the text itself do
+                    let (tables, items): (Vec<_>, Vec<_>) =
+                        items.into_iter().partition(is_converted_table);
+                    let items = if tables.is_empty() {
+                        items
+                            .into_iter()
+                            .chain(std::iter::once(array))
+                            .chain(others)
+                            .collect::<Vec<_>>()
+                    } else {
+                        items
+                            .into_iter()
+                            .chain(others)
+                            .chain(tables)
+                            .chain(std::iter::once(array))
+                            .collect::<Vec<_>>()
+                    };
                     return Ok(Value::AnyOf(items, str.to_string()));
                 }
             },
@@ -102,30 +266,56 @@ pub fn parse(str: &str, mut options: ParseOptions) -> Result<Value> {
         }
     }
 
+    if options.allow_calls {
+        let calls = call_parser::parse(str, &options);
+        if !calls.is_empty() {
+            return Ok(found(calls, Fixes::InterpretedCall, str));
+        }
+    }
+
+    // Tables and YAML are offered after any JSON found in the text, so that
+    // JSON written next to a table or after a `Reasoning: ...` line wins
+    let mut fallbacks = vec![];
+    if options.allow_tables {
+        let tables = markdown_table::parse(str, &options);
+        if !tables.is_empty() {
+            if let Value::AnyOf(items, _) = found(tables, Fixes::ConvertedTable, str) {
+                fallbacks = items;
+            }
+        }
+    }
+    if options.allow_yaml {
+        match yaml_parser::parse(str, &options, false) {
+            Ok(v) => fallbacks.push(Value::FixedJson(v.into(), vec![Fixes::InterpretedYaml])),
+            Err(e) => {
+                log::debug!("Not YAML: {:?}", e);
+            }
+        }
+    }
+
     if options.all_finding_all_json_objects {
         match multi_json_parser::parse(str, &options) {
             Ok(items) => match items.len() {
                 0 => {}
                 1 => {
-                    return Ok(Value::AnyOf(
-                        vec![Value::FixedJson(
-                            items
-                                .into_iter()
-                                .next()
-                                .ok_or_else(|| anyhow::anyhow!("Expected 1 item"))?
-                                .into(),
-                            vec![Fixes::GreppedForJSON],
-                        )],
-                        str.to_string(),
-                    ))
+                    let item = Value::FixedJson(
+                        items
+                            .into_iter()
+                            .next()
+                            .ok_or_else(|| anyhow::anyhow!("Expected 1 item"))?
+                            .into(),
+                        vec![Fixes::GreppedForJSON],
+                    );
+                    let items = std::iter::once(item).chain(fallbacks).collect();
+                    return Ok(Value::AnyOf(items, str.to_string()));
                 }
                 _ => {
                     let items_clone = Value::Array(items.clone());
-                    let items = items
+                    let grepped = items
                         .into_iter()
                         .chain(std::iter::once(items_clone))
-                        .map(|v| Value::FixedJson(v.into(), vec![Fixes::GreppedForJSON]))
-                        .collect::<Vec<_>>();
+                        .map(|v| Value::FixedJson(v.into(), vec![Fixes::GreppedForJSON]));
+                    let items = grepped.chain(fallbacks).collect::<Vec<_>>();
                     return Ok(Value::AnyOf(items, str.to_string()));
                 }
             },
@@ -135,6 +325,10 @@ pub fn parse(str: &str, mut options: ParseOptions) -> Result<Value> {
         }
     }
 
+    if !fallbacks.is_empty() {
+        return Ok(Value::AnyOf(fallbacks, str.to_string()));
+    }
+
     if options.allow_fixes {
         match fixing_parser::parse(str, &options) {
             Ok(items) => {
@@ -144,10 +338,14 @@ pub fn parse(str: &str, mut options: ParseOptions) -> Result<Value> {
                         let (v, fixes) = items.into_iter().next().ok_or_else(|| {
                             anyhow::anyhow!("Expected 1 item when performing fixes")
                         })?;
-                        return Ok(Value::AnyOf(
-                            vec![Value::FixedJson(v.into(), fixes)],
-                            str.to_string(),
-                        ));
+                        // Text read back as one unquoted string is prose, which
+                        // the later stages handle
+                        if !matches!(&v, Value::String(s) if s == str.trim()) {
+                            return Ok(Value::AnyOf(
+                                vec![Value::FixedJson(v.into(), fixes)],
+                                str.to_string(),
+                            ));
+                        }
                     }
                     _ => {
                         // In the case of multiple JSON objects:
@@ -179,6 +377,19 @@ pub fn parse(str: &str, mut options: ParseOptions) -> Result<Value> {
         }
     }
 
+    if options.allow_prose {
+        if let Some(v) = prose_parser::parse(str, &options) {
+            // The structure is a guess, the text itself stays the first choice.
+            let prose = Value::FixedJson(v.into(), vec![Fixes::StructuredProse]);
+            let items = if options.allow_as_string {
+                vec![Value::String(str.to_string()), prose]
+            } else {
+                vec![prose]
+            };
+            return Ok(Value::AnyOf(items, str.to_string()));
+        }
+    }
+
     if options.allow_as_string {
         return Ok(Value::String(str.to_string()));
     }
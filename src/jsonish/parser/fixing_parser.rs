@@ -1,14 +1,22 @@
+mod cursor;
 mod json_collection;
 mod json_parse_state;
 
 use crate::jsonish::{value::Fixes, Value};
 
-use self::json_parse_state::JsonParseState;
+use std::collections::HashMap;
+
+use self::{cursor::Cursor, json_collection::JsonCollection, json_parse_state::JsonParseState};
 
 use super::ParseOptions;
 use anyhow::Result;
 
-pub fn parse(str: &str, _options: &ParseOptions) -> Result<Vec<(Value, Vec<Fixes>)>> {
+// How many runs of the state machine each tracked hypothesis may cost
+const RUNS_PER_HYPOTHESIS: usize = 8;
+
+type Parsed = Vec<(Value, Vec<Fixes>)>;
+
+pub fn parse(str: &str, options: &ParseOptions) -> Result<Parsed> {
     // Try to fix some common JSON issues
     // - Unquoted single word strings
     // - Single quoted strings
@@ -27,76 +35,185 @@ pub fn parse(str: &str, _options: &ParseOptions) -> Result<Vec<(Value, Vec<Fixes
     // - Unterminated objects
     // - Unterminated strings
 
-    let mut state = JsonParseState::new();
+    let primary = Hypothesis::run(str, options, HashMap::new())?;
+    if options.max_hypotheses == 0 {
+        return Ok(primary.values);
+    }
+    let mut hypotheses = search(str, options, primary, options.max_hypotheses);
 
-    let mut chars = str.char_indices().peekable();
-    while let Some((count, c)) = chars.next() {
-        let peekable = str[count + c.len_utf8()..].char_indices().peekable();
-        match state.process_token(c, peekable) {
-            Ok(increments) => {
-                for _ in 0..increments {
-                    chars.next();
+    if hypotheses.len() == 1 || hypotheses.iter().any(|h| h.values.len() != 1) {
+        return Ok(hypotheses.swap_remove(0).values);
+    }
+
+    let candidates = hypotheses
+        .into_iter()
+        .flat_map(|h| {
+            let is_alternative = !h.overrides.is_empty();
+            h.values.into_iter().map(move |(v, mut fixes)| {
+                if is_alternative {
+                    fixes.push(Fixes::AlternativeInterpretation);
                 }
-            }
-            Err(e) => {
-                return Err(e);
+                Value::FixedJson(v.into(), fixes)
+            })
+        })
+        .collect();
+    Ok(vec![(Value::AnyOf(candidates, str.to_string()), vec![])])
+}
+
+/// Beam search over the guesses: every live hypothesis forks at each guess it
+/// made, the `max` cleanest children stay live, and the `max` cleanest
+/// hypotheses overall are returned best first.
+fn search(str: &str, options: &ParseOptions, primary: Hypothesis, max: usize) -> Vec<Hypothesis> {
+    let budget = max.saturating_mul(RUNS_PER_HYPOTHESIS);
+    let mut runs = 1;
+    let mut hypotheses = vec![primary];
+    let mut live = vec![0];
+
+    while !live.is_empty() && runs < budget {
+        let forks = live
+            .iter()
+            .flat_map(|&i| hypotheses[i].forks())
+            .collect::<Vec<_>>();
+        let mut children: Vec<Hypothesis> = vec![];
+        for overrides in forks.into_iter().take(budget - runs) {
+            runs += 1;
+            match Hypothesis::run(str, options, overrides) {
+                Ok(h)
+                    if !hypotheses
+                        .iter()
+                        .chain(&children)
+                        .any(|o| o.values == h.values) =>
+                {
+                    children.push(h)
+                }
+                Ok(_) => {}
+                Err(e) => log::debug!("Hypothesis failed: {:?}", e),
             }
         }
+        children.sort_by_key(|h| h.penalty);
+        children.truncate(max);
+        live = (hypotheses.len()..hypotheses.len() + children.len()).collect();
+        hypotheses.extend(children);
     }
 
-    // If we still have a collection open, close it
-    while !state.collection_stack.is_empty() {
-        state.complete_collection();
-    }
-
-    // Determine what to return.
-
-    match state.completed_values.len() {
-        0 => Err(anyhow::anyhow!("No JSON objects found")),
-        1 => state
-            .completed_values
-            .pop()
-            .map(|(_name, value, fixes)| Ok(vec![(value, fixes)]))
-            .unwrap_or(Err(anyhow::anyhow!("Failed to pop completed value"))),
-        _ => {
-            if state.completed_values.iter().all(|f| f.0 == "string") {
-                // If all the values are strings, return them as an array of strings
-                Ok(vec![(
-                    Value::Array(
-                        state
-                            .completed_values
-                            .into_iter()
-                            .map(|f| Value::FixedJson(f.1.into(), f.2))
-                            .collect(),
-                    ),
-                    vec![Fixes::InferredArray],
-                )])
-            } else {
-                // Filter for only objects and arrays
-                let values: Vec<(Value, Vec<Fixes>)> = state
-                    .completed_values
-                    .into_iter()
-                    .filter_map(|f| {
-                        if f.0 == "Object" || f.0 == "Array" {
-                            Some((f.1, f.2))
-                        } else {
-                            None
-                        }
-                    })
-                    .collect();
-                match values.len() {
-                    0 => Err(anyhow::anyhow!("No JSON objects found")),
-                    _ => Ok(values),
+    // Ties go to the hypothesis closest to the default interpretation
+    hypotheses.sort_by_key(|h| (h.penalty, h.overrides.len()));
+    hypotheses.truncate(max);
+    hypotheses
+}
+
+/// One run of the state machine with some of its guesses overridden.
+struct Hypothesis {
+    overrides: HashMap<usize, bool>,
+    values: Parsed,
+    // Guesses made during the run: (offset, whether a value was closed)
+    decisions: Vec<(usize, bool)>,
+    // How much repairing the run needed, lower is cleaner
+    penalty: usize,
+}
+
+impl Hypothesis {
+    /// The overrides of hypotheses that take the other branch at one guess
+    /// made after the last guess this one overrides, so that every
+    /// combination of overrides is only produced once.
+    fn forks(&self) -> impl Iterator<Item = HashMap<usize, bool>> + '_ {
+        let last = self.overrides.keys().max().copied();
+        self.decisions
+            .iter()
+            .filter(move |(at, _)| !matches!(last, Some(last) if *at <= last))
+            .map(move |&(at, close)| {
+                let mut overrides = self.overrides.clone();
+                overrides.insert(at, !close);
+                overrides
+            })
+    }
+
+    fn run(str: &str, options: &ParseOptions, overrides: HashMap<usize, bool>) -> Result<Self> {
+        let mut state = JsonParseState::new(options, overrides.clone());
+
+        let mut cursor = Cursor::new(str);
+        while let Some(c) = cursor.next() {
+            state.process_token(c, &mut cursor)?;
+        }
+
+        // Anything left open at the end of the input had to be repaired
+        let unclosed = state
+            .collection_stack
+            .iter()
+            .filter(|(c, _)| {
+                !matches!(
+                    c,
+                    JsonCollection::TrailingComment(_) | JsonCollection::BlockComment(_)
+                )
+            })
+            .count();
+
+        // If we still have a collection open, close it
+        while !state.collection_stack.is_empty() {
+            state.complete_collection();
+        }
+        state.flush_comments();
+
+        // Determine what to return.
+        let decisions = std::mem::take(&mut state.decisions);
+        let extra_values = state.completed_values.len().saturating_sub(1);
+        let values = match state.completed_values.len() {
+            0 => Err(anyhow::anyhow!("No JSON objects found")),
+            1 => state
+                .completed_values
+                .pop()
+                .map(|(_name, value, fixes)| Ok(vec![(value, fixes)]))
+                .unwrap_or(Err(anyhow::anyhow!("Failed to pop completed value"))),
+            _ => {
+                if state.completed_values.iter().all(|f| f.0 == "string") {
+                    // If all the values are strings, return them as an array of strings
+                    Ok(vec![(
+                        Value::Array(
+                            state
+                                .completed_values
+                                .into_iter()
+                                .map(|f| Value::FixedJson(f.1.into(), f.2))
+                                .collect(),
+                        ),
+                        vec![Fixes::InferredArray],
+                    )])
+                } else {
+                    // Filter for only objects and arrays
+                    let values: Vec<(Value, Vec<Fixes>)> = state
+                        .completed_values
+                        .into_iter()
+                        .filter_map(|f| {
+                            if f.0 == "Object" || f.0 == "Array" {
+                                Some((f.1, f.2))
+                            } else {
+                                None
+                            }
+                        })
+                        .collect();
+                    match values.len() {
+                        0 => Err(anyhow::anyhow!("No JSON objects found")),
+                        _ => Ok(values),
+                    }
                 }
             }
-        }
+        };
+
+        let values = values?;
+        let penalty =
+            unclosed + extra_values + values.iter().map(|(_, fixes)| fixes.len()).sum::<usize>();
+        Ok(Hypothesis {
+            overrides,
+            values,
+            decisions,
+            penalty,
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::jsonish::{ParseOptions, Value};
+    use crate::jsonish::{HolePolicy, ParseOptions, Value};
 
     #[test]
     fn test_partial_array() {
@@ -162,4 +279,282 @@ mod tests {
             _ => panic!("Expected object"),
         }
     }
+
+    #[test]
+    fn test_missing_colon() {
+        let opts = ParseOptions::default();
+        let vals = parse(r#"{"a" 1, "b" "two", "c" [3]}"#, &opts).unwrap();
+        assert_eq!(
+            vals[0],
+            (
+                Value::Object(vec![
+                    ("a".to_string(), Value::Number(1.into())),
+                    ("b".to_string(), Value::String("two".to_string())),
+                    ("c".to_string(), Value::Array(vec![Value::Number(3.into())])),
+                ]),
+                vec![Fixes::InsertedColon; 3]
+            )
+        );
+    }
+
+    #[test]
+    fn test_alternative_separators() {
+        let opts = ParseOptions::default();
+        for (input, separator) in [
+            ("{a = 1, b = -2}", "="),
+            (r#"{"a" => 1, "b" => -2}"#, "=>"),
+            ("{a -> 1, b -> -2}", "->"),
+        ] {
+            let vals = parse(input, &opts).unwrap();
+            assert_eq!(
+                vals[0],
+                (
+                    Value::Object(vec![
+                        ("a".to_string(), Value::Number(1.into())),
+                        ("b".to_string(), Value::Number((-2).into())),
+                    ]),
+                    vec![Fixes::ReplacedSeparator(separator.to_string()); 2]
+                ),
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_unquoted_value_termination() {
+        let opts = ParseOptions::default();
+        let vals = parse(
+            "{url: https://a.b/c?x=1,2, time: 12:30, expr: max(a, b), note: Hello, world}",
+            &opts,
+        )
+        .unwrap();
+        let Value::Object(fields) = &vals[0].0 else {
+            panic!("Expected object, got {:?}", vals);
+        };
+        let fields: Vec<(&str, &Value)> = fields.iter().map(|(k, v)| (k.as_str(), v)).collect();
+        assert_eq!(
+            fields,
+            vec![
+                ("url", &Value::String("https://a.b/c?x=1,2".to_string())),
+                ("time", &Value::String("12:30".to_string())),
+                ("expr", &Value::String("max(a, b)".to_string())),
+                ("note", &Value::String("Hello, world".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ambiguous_value_yields_alternatives() {
+        let input = "{a: hello\n b: 2}";
+
+        // By default the input is parsed once, without alternatives
+        let vals = parse(input, &ParseOptions::default()).unwrap();
+        assert!(matches!(vals[..], [(Value::Object(_), _)]), "{vals:?}");
+
+        let opts = ParseOptions::default().track_hypotheses(3);
+        let vals = parse(input, &opts).unwrap();
+        let Value::AnyOf(candidates, _) = &vals[0].0 else {
+            panic!("Expected alternatives, got {:?}", vals);
+        };
+        assert_eq!(
+            candidates,
+            &vec![
+                Value::FixedJson(
+                    Value::Object(vec![
+                        ("a".to_string(), Value::String("hello".to_string())),
+                        ("b".to_string(), Value::Number(2.into())),
+                    ])
+                    .into(),
+                    vec![]
+                ),
+                Value::FixedJson(
+                    Value::Object(vec![(
+                        "a".to_string(),
+                        Value::String("hello\n b: 2".to_string())
+                    )])
+                    .into(),
+                    vec![Fixes::AlternativeInterpretation]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_hypotheses_ranked_by_cleanliness() {
+        let input = r#"{"a": "He said "hi, there", "b": 1}"#;
+        let clean = Value::Object(vec![
+            (
+                "a".to_string(),
+                Value::String(r#"He said "hi, there"#.to_string()),
+            ),
+            ("b".to_string(), Value::Number(1.into())),
+        ]);
+
+        // By default the quote count guess wins, leaving the string unterminated
+        let default = parse(input, &ParseOptions::default()).unwrap();
+        assert_ne!(default[0].0, clean);
+
+        // Tracking hypotheses ranks the interpretation that parses cleanly first
+        let tracked = match parse(input, &ParseOptions::default().track_hypotheses(2))
+            .unwrap()
+            .remove(0)
+            .0
+        {
+            Value::AnyOf(candidates, _) => candidates,
+            v => panic!("Expected alternatives, got {:?}", v),
+        };
+        assert_eq!(tracked.len(), 2);
+        assert_eq!(
+            tracked[0],
+            Value::FixedJson(clean.into(), vec![Fixes::AlternativeInterpretation])
+        );
+    }
+
+    #[test]
+    fn test_lookahead_past_doubled_quotes() {
+        // A `""` inside a triple quoted string does not close it, nor does
+        // a ` `` ` inside a triple backtick string
+        let opts = ParseOptions::default();
+        for (input, expected) in [
+            (r#"{"a": """x ""q"" y""", "b": 2}"#, r#"x ""q"" y"#),
+            (r#"{"a": ```x ``q`` y```, "b": 2}"#, "x ``q`` y"),
+        ] {
+            let vals = parse(input, &opts).unwrap();
+            assert_eq!(
+                vals[0].0,
+                Value::Object(vec![
+                    ("a".to_string(), Value::String(expected.to_string())),
+                    ("b".to_string(), Value::Number(2.into())),
+                ]),
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_multibyte_characters_and_escapes() {
+        let opts = ParseOptions::default();
+        let vals = parse(
+            r#"{a: café☕, b: "é😀\n", c: [naïve, 1], d: "\u+041\u0041"}"#,
+            &opts,
+        )
+        .unwrap();
+        assert_eq!(
+            vals[0].0,
+            Value::Object(vec![
+                ("a".to_string(), Value::String("café☕".to_string())),
+                ("b".to_string(), Value::String("é😀\n".to_string())),
+                (
+                    "c".to_string(),
+                    Value::Array(vec![
+                        Value::String("naïve".to_string()),
+                        Value::Number(1.into())
+                    ])
+                ),
+                // Only four hex digits make a `\u` escape
+                ("d".to_string(), Value::String(r"\u+041A".to_string())),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_mismatched_closers() {
+        let opts = ParseOptions::default();
+        let array = || Value::Array(vec![Value::Number(1.into()), Value::Number(2.into())]);
+
+        // More follows, so the `}` was meant to be a `]`
+        let vals = parse(r#"{"a": [1, 2}, "b": 3}"#, &opts.track_hypotheses(2)).unwrap();
+        let Value::AnyOf(candidates, _) = &vals[0].0 else {
+            panic!("Expected alternatives, got {:?}", vals);
+        };
+        assert_eq!(
+            candidates[0],
+            Value::FixedJson(
+                Value::Object(vec![
+                    ("a".to_string(), array()),
+                    ("b".to_string(), Value::Number(3.into())),
+                ])
+                .into(),
+                vec![Fixes::ReplacedCloser { from: '}', to: ']' }]
+            )
+        );
+        assert_eq!(
+            candidates[1],
+            Value::FixedJson(
+                Value::Object(vec![("a".to_string(), array())]).into(),
+                vec![Fixes::InsertedCloser(']'), Fixes::AlternativeInterpretation]
+            )
+        );
+
+        // Nothing is open that `]` could close
+        let vals = parse(r#"{"a": 1], "b": 2}"#, &opts).unwrap();
+        assert_eq!(
+            vals[0],
+            (
+                Value::Object(vec![
+                    ("a".to_string(), Value::Number(1.into())),
+                    ("b".to_string(), Value::Number(2.into())),
+                ]),
+                vec![Fixes::DroppedCloser(']')]
+            )
+        );
+    }
+
+    #[test]
+    fn test_hole_policies() {
+        let input = r#"{"a": , "b": [1,,3], "c": ...}"#;
+        let number = |n: i32| Value::Number(n.into());
+        let missing = |key: Option<&str>| Fixes::MissingValue(key.map(str::to_string));
+        let fixes = vec![
+            missing(Some("a")),
+            missing(None),
+            Fixes::ElidedValue(Some("c".to_string())),
+        ];
+
+        let vals = parse(input, &ParseOptions::default()).unwrap();
+        assert_eq!(
+            vals[0],
+            (
+                Value::Object(vec![
+                    ("a".to_string(), Value::Null),
+                    (
+                        "b".to_string(),
+                        Value::Array(vec![number(1), Value::Null, number(3)])
+                    ),
+                    ("c".to_string(), Value::Null),
+                ]),
+                fixes.clone()
+            )
+        );
+
+        let vals = parse(input, &ParseOptions::default().fill_holes(HolePolicy::Drop)).unwrap();
+        assert_eq!(
+            vals[0],
+            (
+                Value::Object(vec![(
+                    "b".to_string(),
+                    Value::Array(vec![number(1), number(3)])
+                )]),
+                fixes.clone()
+            )
+        );
+
+        let vals = parse(
+            input,
+            &ParseOptions::default().fill_holes(HolePolicy::Elided),
+        )
+        .unwrap();
+        let marker = |fix: &Fixes| Value::FixedJson(Value::Null.into(), vec![fix.clone()]);
+        assert_eq!(
+            vals[0].0,
+            Value::Object(vec![
+                ("a".to_string(), marker(&fixes[0])),
+                (
+                    "b".to_string(),
+                    Value::Array(vec![number(1), marker(&fixes[1]), number(3)])
+                ),
+                ("c".to_string(), marker(&fixes[2])),
+            ])
+        );
+    }
 }
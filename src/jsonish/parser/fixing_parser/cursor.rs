@@ -0,0 +1,100 @@
+/// The read position of the fixing parser in its input.
+///
+/// Looking ahead (`peek`, `peek_nth`, `starts_with`, `lookahead`) never moves
+/// the cursor; only `next`, `next_if` and `advance` consume characters.
+#[derive(Clone, Debug)]
+pub struct Cursor<'a> {
+    input: &'a str,
+    offset: usize,
+}
+
+impl<'a> Cursor<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Cursor { input, offset: 0 }
+    }
+
+    /// Byte offset of the next unread character.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.offset >= self.input.len()
+    }
+
+    /// The unread part of the input.
+    pub fn rest(&self) -> &'a str {
+        &self.input[self.offset..]
+    }
+
+    pub fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    /// The `n`th unread character, `peek_nth(0)` being `peek()`.
+    pub fn peek_nth(&self, n: usize) -> Option<char> {
+        self.rest().chars().nth(n)
+    }
+
+    pub fn starts_with(&self, s: &str) -> bool {
+        self.rest().starts_with(s)
+    }
+
+    /// The unread characters with their byte offsets in the input.
+    pub fn lookahead(&self) -> impl Iterator<Item = (usize, char)> + Clone + 'a {
+        let offset = self.offset;
+        self.rest()
+            .char_indices()
+            .map(move |(idx, c)| (offset + idx, c))
+    }
+
+    pub fn next_if(&mut self, f: impl FnOnce(char) -> bool) -> Option<char> {
+        match self.peek() {
+            Some(c) if f(c) => self.next(),
+            _ => None,
+        }
+    }
+
+    /// Consumes `n` characters, or what is left of the input if shorter.
+    pub fn advance(&mut self, n: usize) {
+        for _ in 0..n {
+            if self.next().is_none() {
+                break;
+            }
+        }
+    }
+}
+
+impl Iterator for Cursor<'_> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.offset += c.len_utf8();
+        Some(c)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookahead_does_not_consume() {
+        let mut cursor = Cursor::new("é\"\"x");
+        assert_eq!(cursor.next(), Some('é'));
+        assert_eq!(cursor.offset(), 2);
+
+        assert_eq!(cursor.peek(), Some('"'));
+        assert_eq!(cursor.peek_nth(2), Some('x'));
+        assert!(cursor.starts_with("\"\""));
+        assert_eq!(cursor.lookahead().next(), Some((2, '"')));
+        assert_eq!(cursor.offset(), 2);
+
+        assert_eq!(cursor.next_if(|c| c == 'x'), None);
+        cursor.advance(2);
+        assert_eq!(cursor.rest(), "x");
+        cursor.advance(5);
+        assert!(cursor.is_empty());
+    }
+}
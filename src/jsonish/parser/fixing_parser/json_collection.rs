@@ -57,7 +57,7 @@ impl From<JsonCollection> for Option<Value> {
             JsonCollection::Object(keys, values) => {
                 // log::debug!("keys: {:?}", keys);
                 let mut object = Vec::new();
-                for (key, value) in keys.into_iter().zip(values.into_iter()) {
+                for (key, value) in keys.into_iter().zip(values) {
                     object.push((key, value));
                 }
                 Value::Object(object)
@@ -67,7 +67,7 @@ impl From<JsonCollection> for Option<Value> {
             JsonCollection::TripleQuotedString(s) => Value::String(s),
             JsonCollection::SingleQuotedString(s) => Value::String(s),
             JsonCollection::TripleBacktickString { content, .. } => {
-                let Some((fenced_codeblock_info, codeblock_contents)) = content.split_once("\n")
+                let Some((_fenced_codeblock_info, codeblock_contents)) = content.split_once("\n")
                 else {
                     return Some(Value::String(content));
                 };
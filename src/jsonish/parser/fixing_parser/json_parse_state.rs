@@ -1,22 +1,56 @@
-use std::iter::Peekable;
+use std::collections::HashMap;
 
-use crate::jsonish::{value::Fixes, Value};
+use crate::jsonish::{
+    parser::{HolePolicy, ParseOptions},
+    value::Fixes,
+    Value,
+};
 use anyhow::Result;
 
-use super::json_collection::JsonCollection;
+use super::{cursor::Cursor, json_collection::JsonCollection};
+
+// Deeper documents are rejected: the resulting `Value` is walked recursively
+// and would overflow the stack.
+const MAX_NESTING: usize = 512;
 
 pub struct JsonParseState {
     pub collection_stack: Vec<(JsonCollection, Vec<Fixes>)>,
 
     // Technically we may find multiple values in a single string
     pub completed_values: Vec<(&'static str, Value, Vec<Fixes>)>,
+
+    keep_comments: bool,
+    hole_policy: HolePolicy,
+    // Comments waiting for the next value they precede
+    pending_comments: Vec<String>,
+    // Whether a newline / comma was seen since the last value was completed,
+    // which decides if a comment trails that value or precedes the next one.
+    newline_since_value: bool,
+    comma_since_value: bool,
+
+    // A key was completed but no `:` (or substitute) followed it yet
+    separator_pending: bool,
+
+    // Guesses made at ambiguous points: (offset, whether a value was closed)
+    pub decisions: Vec<(usize, bool)>,
+    // Guesses to make differently from the default, used to re-run the
+    // parser and produce alternative interpretations
+    overrides: HashMap<usize, bool>,
 }
 
 impl JsonParseState {
-    pub fn new() -> Self {
+    pub fn new(options: &ParseOptions, overrides: HashMap<usize, bool>) -> Self {
         JsonParseState {
             collection_stack: vec![],
             completed_values: vec![],
+            keep_comments: options.keep_comments,
+            hole_policy: options.hole_policy,
+            pending_comments: vec![],
+            newline_since_value: false,
+            comma_since_value: false,
+            separator_pending: false,
+            decisions: vec![],
+            overrides,
         }
     }
 
@@ -28,41 +62,243 @@ impl JsonParseState {
 
         let name = collection.name();
 
+        let collection = match collection {
+            JsonCollection::TrailingComment(comment) => {
+                if self.keep_comments {
+                    // `"a": 1, // about a`
+                    let trails = !self.newline_since_value;
+                    self.attach_comment(comment.trim().to_string(), trails);
+                }
+                return;
+            }
+            JsonCollection::BlockComment(comment) => {
+                if self.keep_comments {
+                    // `[1 /* about 1 */, /* about 2 */ 2]`
+                    let trails = !self.newline_since_value && !self.comma_since_value;
+                    self.attach_comment(comment.trim().to_string(), trails);
+                }
+                return;
+            }
+            // Comments right before a closing bracket belong to the last item
+            JsonCollection::Object(keys, mut values)
+                if !self.pending_comments.is_empty() && keys.len() == values.len() =>
+            {
+                if let Some(last) = values.pop() {
+                    values.push(last.with_comments(std::mem::take(&mut self.pending_comments)));
+                }
+                JsonCollection::Object(keys, values)
+            }
+            JsonCollection::Array(mut values) if !self.pending_comments.is_empty() => {
+                if let Some(last) = values.pop() {
+                    values.push(last.with_comments(std::mem::take(&mut self.pending_comments)));
+                }
+                JsonCollection::Array(values)
+            }
+            collection => collection,
+        };
+
+        // `"a": ...`
+        if matches!(&collection, JsonCollection::UnquotedString(s) if is_ellipsis(s.trim())) {
+            match self.collection_stack.last_mut() {
+                // `{"a": 1, ...}` elides whole members
+                Some((JsonCollection::Object(keys, values), fixes))
+                    if keys.len() == values.len() =>
+                {
+                    fixes.push(Fixes::ElidedValue(None));
+                }
+                _ => self.fill_hole(true),
+            }
+            return;
+        }
+
+        // An unquoted value such as `7\n` may have swallowed the line break
+        // that ended it
+        let ends_line = matches!(
+            &collection,
+            JsonCollection::UnquotedString(s) if s[s.trim_end().len()..].contains('\n')
+        );
+
         let value: Value = match collection.into() {
             Some(value) => value,
             None => return,
         };
 
-        if let Some((last, _fixes)) = self.collection_stack.last_mut() {
-            match last {
-                JsonCollection::Object(keys, values) => {
-                    if keys.len() == values.len() {
-                        match value {
-                            Value::String(s) => keys.push(s),
-                            Value::AnyOf(_, s) => keys.push(s),
-                            _ => keys.push(value.to_string()),
-                        }
-                    } else {
-                        values.push(value);
+        match self.collection_stack.last_mut() {
+            Some((JsonCollection::Object(keys, values), parent_fixes)) => {
+                parent_fixes.extend(fixes);
+                if keys.len() == values.len() {
+                    match value {
+                        Value::String(s) => keys.push(s),
+                        Value::AnyOf(_, s) => keys.push(s),
+                        _ => keys.push(value.to_string()),
                     }
+                    self.separator_pending = true;
+                } else {
+                    values.push(value.with_comments(std::mem::take(&mut self.pending_comments)));
+                    self.newline_since_value = ends_line;
+                    self.comma_since_value = false;
+                    self.separator_pending = false;
                 }
-                JsonCollection::Array(values) => {
-                    values.push(value);
-                }
-                _ => {
-                    // TODO: this should never happen as we should only be pushing objects and arrays
-                    panic!(
-                        "Unexpected value: {:?} in collection stack: {:?}",
-                        value, last
-                    );
+            }
+            Some((JsonCollection::Array(values), parent_fixes)) => {
+                parent_fixes.extend(fixes);
+                values.push(value.with_comments(std::mem::take(&mut self.pending_comments)));
+                self.newline_since_value = ends_line;
+                self.comma_since_value = false;
+            }
+            // Only objects and arrays hold values; anything else would be a
+            // bug in the state machine, so keep the value on its own rather
+            // than lose it.
+            _ => {
+                let value = value.with_comments(std::mem::take(&mut self.pending_comments));
+                self.completed_values.push((name, value, fixes));
+                self.newline_since_value = ends_line;
+                self.comma_since_value = false;
+            }
+        }
+    }
+
+    /// Closes the object or array on top of the stack on meeting `closer`,
+    /// repairing a closer of the wrong kind.
+    fn close_collection(&mut self, closer: char, cursor: &Cursor) {
+        // `{"a": }`
+        if matches!(
+            self.collection_stack.last(),
+            Some((JsonCollection::Object(keys, values), _)) if keys.len() > values.len()
+        ) {
+            self.fill_hole(false);
+        }
+
+        let Some(expected) = self.collection_stack.last().and_then(|(c, _)| closer_of(c)) else {
+            return;
+        };
+        if closer == expected {
+            self.complete_collection();
+            return;
+        }
+
+        let Some(depth) = self
+            .collection_stack
+            .iter()
+            .rev()
+            .position(|(c, _)| closes(c, closer))
+        else {
+            // `{"a": 1], "b": 2}`
+            self.push_fix(Fixes::DroppedCloser(closer));
+            return;
+        };
+
+        // `{"a": [1, 2}, "b": 3}` mistyped the `]`, while in `{"a": [1, 2} ok`
+        // the array was left open.
+        let continues = matches!(
+            cursor
+                .lookahead()
+                .map(|(_, c)| c)
+                .find(|c| !c.is_whitespace()),
+            Some(',' | '}' | ']')
+        );
+        let at = cursor.offset() - closer.len_utf8();
+        if self.decide(at, continues) {
+            self.push_fix(Fixes::ReplacedCloser {
+                from: closer,
+                to: expected,
+            });
+            self.complete_collection();
+        } else {
+            for _ in 0..depth {
+                if let Some(c) = self.collection_stack.last().and_then(|(c, _)| closer_of(c)) {
+                    self.push_fix(Fixes::InsertedCloser(c));
                 }
+                self.complete_collection();
+            }
+            self.complete_collection();
+        }
+    }
+
+    /// Fills the value missing from the object or array on top of the stack
+    /// according to the hole policy.
+    fn fill_hole(&mut self, elided: bool) {
+        let Some((collection, fixes)) = self.collection_stack.last_mut() else {
+            return;
+        };
+        let key = match collection {
+            JsonCollection::Object(keys, values) if keys.len() > values.len() => {
+                keys.last().cloned()
             }
+            JsonCollection::Array(_) => None,
+            _ => return,
+        };
+        let fix = if elided {
+            Fixes::ElidedValue(key)
         } else {
-            self.completed_values.push((name, value, fixes));
+            Fixes::MissingValue(key)
+        };
+
+        let value = match self.hole_policy {
+            HolePolicy::Null => Some(Value::Null),
+            HolePolicy::Drop => None,
+            HolePolicy::Elided => Some(Value::FixedJson(Value::Null.into(), vec![fix.clone()])),
+        };
+        match (collection, value) {
+            (JsonCollection::Object(_, values) | JsonCollection::Array(values), Some(value)) => {
+                values.push(value)
+            }
+            (JsonCollection::Object(keys, _), None) => {
+                keys.pop();
+            }
+            _ => {}
         }
+        fixes.push(fix);
+        self.separator_pending = false;
+        self.comma_since_value = false;
     }
 
-    fn consume(&mut self, token: char) -> Result<usize> {
+    /// Whether `closer` closes an object or array open on the stack.
+    fn closes_open_collection(&self, closer: char) -> bool {
+        self.collection_stack.iter().any(|(c, _)| closes(c, closer))
+    }
+
+    fn push_fix(&mut self, fix: Fixes) {
+        if let Some((_, fixes)) = self.collection_stack.last_mut() {
+            fixes.push(fix);
+        }
+    }
+
+    fn replace_separator(&mut self, separator: &str) {
+        self.separator_pending = false;
+        self.push_fix(Fixes::ReplacedSeparator(separator.to_string()));
+    }
+
+    fn attach_comment(&mut self, comment: String, trails_previous: bool) {
+        if trails_previous {
+            let last = match self.collection_stack.last_mut() {
+                Some((JsonCollection::Object(keys, values), _)) if keys.len() == values.len() => {
+                    values.last_mut()
+                }
+                Some((JsonCollection::Array(values), _)) => values.last_mut(),
+                Some(_) => None,
+                None => self.completed_values.last_mut().map(|(_, v, _)| v),
+            };
+            if let Some(last) = last {
+                *last = std::mem::replace(last, Value::Null).with_comments(vec![comment]);
+                return;
+            }
+        }
+        self.pending_comments.push(comment);
+    }
+
+    /// Attaches comments found after the last value to it.
+    pub fn flush_comments(&mut self) {
+        if self.pending_comments.is_empty() {
+            return;
+        }
+        let comments = std::mem::take(&mut self.pending_comments);
+        if let Some((_, last, _)) = self.completed_values.last_mut() {
+            *last = std::mem::replace(last, Value::Null).with_comments(comments);
+        }
+    }
+
+    fn consume(&mut self, token: char) -> Result<()> {
         let Some((last, _)) = self.collection_stack.last_mut() else {
             return Err(anyhow::anyhow!(
                 "No collection to consume token: {:?}",
@@ -82,273 +318,267 @@ impl JsonParseState {
                 s.push(token);
             }
             JsonCollection::Object(_, _) | JsonCollection::Array(_) => {
-                panic!("Unexpected token: {:?} in: {:?}", token, last);
+                return Err(anyhow::anyhow!(
+                    "Unexpected token: {:?} in: {:?}",
+                    token,
+                    last
+                ));
             }
         }
-        Ok(0)
+        Ok(())
     }
 
-    fn is_string_complete(&self) -> bool {
-        let Some((JsonCollection::UnquotedString(v), _)) = self.collection_stack.last() else {
-            return false;
-        };
-
-        // Check if the token is a valid json character
-        match v.as_str() {
-            "true" | "false" | "null" => true,
-            _ => {
-                // Check if the token parses as a number
-                if v.parse::<f64>().is_ok() {
-                    return true;
-                }
-                false
-            }
+    /// Moves the next character of the input into the string being read.
+    fn consume_next(&mut self, cursor: &mut Cursor) {
+        if let Some(c) = cursor.next() {
+            let _ = self.consume(c);
         }
     }
 
-    fn should_close_unescaped_string(
-        &mut self,
-        mut next: Peekable<impl Iterator<Item = (usize, char)>>,
-    ) -> Option<usize> {
-        let pos = if self.collection_stack.len() >= 2 {
-            self.collection_stack
-                .get(self.collection_stack.len() - 2)
-                .map(|(c, _)| match c {
-                    JsonCollection::Object(keys, values) => {
-                        if keys.len() == values.len() {
-                            2
-                        } else {
-                            3
-                        }
-                    }
-                    JsonCollection::Array(_) => 4,
-                    _ => 1,
-                })
-                .unwrap()
-        } else {
-            0
-        };
-        match pos {
-            0 => {
+    /// Reads the rest of an unquoted string from `cursor`, stopping before the
+    /// character that ends it. Returns whether the string is complete.
+    fn should_close_unescaped_string(&mut self, cursor: &mut Cursor) -> bool {
+        match self.position() {
+            Position::TopLevel => {
                 // in nothing, so perhaps the first '{' or '[' is the start of a new object or array
-                let mut counter = 0;
-                for (idx, c) in next.by_ref() {
-                    counter = idx;
-                    match c {
-                        // If at some point we find a valid json character, we'll close the string
-                        '{' | '[' => return Some(idx),
-                        x => {
-                            let _ = self.consume(x);
-                        }
+                while let Some(c) = cursor.peek() {
+                    // If at some point we find a valid json character, we'll close the string
+                    if matches!(c, '{' | '[') {
+                        return true;
                     }
+                    self.consume_next(cursor);
                 }
-                Some(counter)
+                true
             }
-            1 => None,
-            2 => {
+            Position::Other => false,
+            Position::ObjectKey => {
                 // in object key
-                let mut counter = 0;
-                for (idx, c) in next.by_ref() {
-                    counter = idx;
+                while let Some(c) = cursor.peek() {
                     match c {
-                        ':' => return Some(idx),
-                        x => {
-                            let _ = self.consume(x);
-                        }
+                        // `{a, b: 1}` has no value for `a`, `{a: 1, ...}` has no key
+                        ':' | '=' | ',' | '}' => return true,
+                        '-' if cursor.peek_nth(1) == Some('>') => return true,
+                        // The colon is missing: `{name "x"}`
+                        '"' | '\'' | '{' | '[' if self.ends_with_word() => return true,
+                        _ => self.consume_next(cursor),
                     }
                 }
-                Some(counter)
+                true
             }
-            3 => {
+            Position::ObjectValue => {
                 // in object value
-                let mut counter = 0;
-                while let Some((idx, c)) = next.next() {
-                    counter = idx;
+                // Brackets opened by the value itself: `max(a, b)`, `see [1, 2]`
+                let mut brackets = vec![];
+                while let Some(c) = cursor.peek() {
+                    let mut rest = cursor.clone();
+                    rest.next();
                     match c {
-                        ',' => {
-                            // Check if we have just numeric values in the string so far.
-                            let Some((JsonCollection::UnquotedString(current_value), _)) =
-                                self.collection_stack.last()
-                            else {
-                                return Some(idx);
-                            };
-
-                            // current value could be a numeric looking things.
-                            let is_numeric = current_value.trim().parse::<f64>().is_ok();
-                            let is_bool = current_value.trim().eq_ignore_ascii_case("true")
-                                || current_value.trim().eq_ignore_ascii_case("false");
-                            let is_null = current_value.trim().eq_ignore_ascii_case("null");
-                            let is_possible_value = is_numeric || is_bool || is_null;
-
-                            if let Some((_, next_c)) = next.peek() {
-                                match next_c {
-                                    '\n' => {
-                                        log::debug!("Closing due to: newline after comma");
-                                        return Some(idx);
-                                    }
-                                    ' ' => {
-                                        log::debug!("Testing for comment after space + comma");
-                                        if is_possible_value {
-                                            return Some(idx);
-                                        }
-                                        // If after the space we have "//" or "/*" or the beginning of a key, we'll close the string
-                                        let mut buffer = ",".to_string();
-                                        let mut anything_but_whitespace = false;
-                                        while let Some((_, next_next_c)) = next.next() {
-                                            anything_but_whitespace = anything_but_whitespace
-                                                || !next_next_c.is_whitespace();
-                                            buffer.push(next_next_c);
-                                            match next_next_c {
-                                                ' ' => {}
-                                                '\n' => {
-                                                    if anything_but_whitespace {
-                                                    } else {
-                                                        // Likely end of the key as the LLM generated a ", " token by mistake instead of a ","
-                                                        // so drop the comma
-                                                        log::debug!("Closing due to: newline after comma + space");
-                                                        return Some(idx);
-                                                    }
-                                                }
-                                                '/' => match next.peek() {
-                                                    Some((_, '/')) => {
-                                                        // This is likely a comment
-                                                        return Some(idx);
-                                                    }
-                                                    Some((_, '*')) => {
-                                                        // This is likely a comment
-                                                        return Some(idx);
-                                                    }
-                                                    _ => {
-                                                        // let _ = self.consume(c);
-                                                    }
-                                                },
-                                                '"' => {
-                                                    // This is likely a new key
-                                                    log::debug!("Closing due to: new key after space + comma");
-                                                    return Some(idx);
-                                                }
-                                                _x => {
-                                                    break;
-                                                }
-                                            }
-                                        }
-                                        for c in buffer.chars() {
-                                            let _ = self.consume(c);
-                                        }
-                                    }
-                                    _ => {
-                                        let _ = self.consume(c);
-                                    }
-                                }
-                            } else {
-                                // Don't include the comma
-                                return Some(idx);
-                            }
+                        '(' | '[' | '{' => brackets.push(c),
+                        ')' | ']' | '}' if brackets.last() == Some(&opening_bracket(c)) => {
+                            brackets.pop();
+                        }
+                        // `]` is a mismatched or stray closer: `{"a": 1]`
+                        '}' | ']' => return true,
+                        ',' if brackets.is_empty()
+                            && self.value_ends_at_comma(cursor.offset(), rest.clone()) =>
+                        {
+                            return true
                         }
-                        '}' => return Some(idx),
-                        x => {
-                            let _ = self.consume(x);
+                        '\n' if self.value_ends_at_newline(cursor.offset(), rest.clone()) => {
+                            return true
                         }
+                        '/' if self.starts_comment(rest.peek()) => return true,
+                        _ => {}
                     }
+                    self.consume_next(cursor);
                 }
-                Some(counter)
+                true
             }
-            4 => {
+            Position::ArrayItem => {
                 // in array
-                let mut counter = 0;
-                for (idx, c) in next {
-                    counter = idx;
+                while let Some(c) = cursor.peek() {
                     match c {
-                        ',' => return Some(idx),
-                        ']' => return Some(idx),
-                        x => {
-                            let _ = self.consume(x);
-                        }
+                        ',' | ']' => return true,
+                        // A mismatched or stray closer: `[1}`
+                        '}' => return true,
+                        '/' if self.starts_comment(cursor.peek_nth(1)) => return true,
+                        _ => self.consume_next(cursor),
                     }
                 }
-                counter += 1; // Indicate that we called next() one time after the final `Some`.
-                Some(counter)
+                true
             }
-            _ => unreachable!("Invalid position"),
         }
     }
 
-    fn should_close_string(
-        &mut self,
-        mut next: Peekable<impl Iterator<Item = (usize, char)>>,
-        closing_char: char,
-    ) -> bool {
-        let (has_some_object, in_object_key, in_object_value, in_array) =
-            if self.collection_stack.len() >= 2 {
-                self.collection_stack
-                    .get(self.collection_stack.len() - 2)
-                    .map(|(c, _)| match c {
-                        JsonCollection::Object(keys, values) => {
-                            if keys.len() == values.len() {
-                                (true, false, false)
-                            } else {
-                                (false, true, true)
-                            }
-                        }
-                        JsonCollection::Array(_) => (false, false, true),
-                        _ => (false, false, false),
-                    })
-                    .map(|(a, b, c)| (true, a, b, c))
-                    .unwrap()
-            } else {
-                (false, false, false, false)
-            };
+    /// Where the collection on top of the stack sits in its parent.
+    fn position(&self) -> Position {
+        let parent = self
+            .collection_stack
+            .len()
+            .checked_sub(2)
+            .and_then(|i| self.collection_stack.get(i));
+        match parent {
+            None => Position::TopLevel,
+            Some((JsonCollection::Object(keys, values), _)) if keys.len() == values.len() => {
+                Position::ObjectKey
+            }
+            Some((JsonCollection::Object(_, _), _)) => Position::ObjectValue,
+            Some((JsonCollection::Array(_), _)) => Position::ArrayItem,
+            Some(_) => Position::Other,
+        }
+    }
 
-            let closing_char_count = if closing_char == '"' {
-                // count the number of quotes in the string
-                let (last, _) = self.collection_stack.last().unwrap();
-                match last {
-                    JsonCollection::QuotedString(s, ..) => {
-                        let mut count = 0;
-                        // Iterate with indices so we can look backwards
-                        for (i, c) in s.char_indices() {
-                            if c == '"' {
-                                // Count consecutive backslashes immediately preceding this quote
-                                let mut backslash_count = 0;
-                                let mut j = i;
-                                while j > 0 {
-                                    j -= 1;
-                                    if s.as_bytes()[j] == b'\\' {
-                                        backslash_count += 1;
-                                    } else {
-                                        break;
-                                    }
-                                }
-                                // Only count this quote if the number of backslashes is even
-                                if backslash_count % 2 == 0 {
-                                    count += 1;
+    /// Decides whether a `,` met while reading an unquoted object value ends
+    /// the value, based on what follows it.
+    fn value_ends_at_comma(&mut self, at: usize, mut rest: Cursor) -> bool {
+        let Some((JsonCollection::UnquotedString(current), _)) = self.collection_stack.last()
+        else {
+            return true;
+        };
+        if is_literal(current.trim()) {
+            return true;
+        }
+        // `note: Hello, world: 1` could also be a note followed by a `world` key
+        let is_prose = current.trim().contains(char::is_whitespace);
+
+        while rest.next_if(|c| c == ' ' || c == '\t').is_some() {}
+        match rest.peek() {
+            None | Some('}' | '"') => true,
+            Some('/') => matches!(rest.peek_nth(1), Some('/' | '*')),
+            Some('\n' | '\r') => {
+                while rest.next_if(char::is_whitespace).is_some() {}
+                match rest.peek() {
+                    None | Some('}' | '"') => true,
+                    _ if looks_like_key(rest.lookahead()) => true,
+                    // Either multi-line prose or the start of a key we don't recognise
+                    _ => self.decide(at, false),
+                }
+            }
+            _ if looks_like_key(rest.lookahead()) => {
+                if is_prose {
+                    self.decide(at, true)
+                } else {
+                    true
+                }
+            }
+            _ => false,
+        }
+    }
+
+    /// Decides whether a line break met while reading an unquoted object value
+    /// ends the value although no comma was written.
+    fn value_ends_at_newline(&mut self, at: usize, mut rest: Cursor) -> bool {
+        while rest.next_if(char::is_whitespace).is_some() {}
+        match rest.peek() {
+            Some('"') => self.decide(at, true),
+            Some(_) if looks_like_key(rest.lookahead()) => self.decide(at, true),
+            _ => false,
+        }
+    }
+
+    /// Records a guess made at byte offset `at` of the input, honouring any
+    /// override requested for a re-run.
+    fn decide(&mut self, at: usize, close: bool) -> bool {
+        let close = self.overrides.get(&at).copied().unwrap_or(close);
+        self.decisions.push((at, close));
+        close
+    }
+
+    /// Whether the unquoted string being read ends with a word followed by
+    /// whitespace.
+    fn ends_with_word(&self) -> bool {
+        matches!(
+            self.collection_stack.last(),
+            Some((JsonCollection::UnquotedString(current), _))
+                if current.ends_with(char::is_whitespace) && !current.trim().is_empty()
+        )
+    }
+
+    /// Whether a `/` met while reading an unquoted value starts a comment
+    /// (`7 // because ...`) rather than belonging to the value (`https://...`).
+    /// Only done when keeping comments, so default parses are unchanged.
+    fn starts_comment(&self, next: Option<char>) -> bool {
+        if !self.keep_comments || !matches!(next, Some('/' | '*')) {
+            return false;
+        }
+        let Some((JsonCollection::UnquotedString(current), _)) = self.collection_stack.last()
+        else {
+            return false;
+        };
+        current.ends_with(char::is_whitespace) || is_literal(current.trim())
+    }
+
+    fn should_close_string(&mut self, cursor: &Cursor, closing_char: char) -> bool {
+        let mut next = cursor.lookahead().peekable();
+        let (has_some_object, in_object_key, in_object_value, in_array) = match self.position() {
+            Position::TopLevel => (false, false, false, false),
+            Position::ObjectKey => (true, true, false, false),
+            Position::ObjectValue => (true, false, true, true),
+            Position::ArrayItem => (true, false, false, true),
+            Position::Other => (true, false, false, false),
+        };
+
+        let closing_char_count = if closing_char == '"' {
+            // count the number of quotes in the string
+            let Some((last, _)) = self.collection_stack.last() else {
+                // No string is open, so there is nothing to keep reading
+                return true;
+            };
+            match last {
+                JsonCollection::QuotedString(s, ..) => {
+                    let mut count = 0;
+                    // Iterate with indices so we can look backwards
+                    for (i, c) in s.char_indices() {
+                        if c == '"' {
+                            // Count consecutive backslashes immediately preceding this quote
+                            let mut backslash_count = 0;
+                            let mut j = i;
+                            while j > 0 {
+                                j -= 1;
+                                if s.as_bytes()[j] == b'\\' {
+                                    backslash_count += 1;
+                                } else {
+                                    break;
                                 }
                             }
+                            // Only count this quote if the number of backslashes is even
+                            if backslash_count % 2 == 0 {
+                                count += 1;
+                            }
                         }
-                        count
                     }
-                    _ => 0,
+                    count
                 }
-            } else {
-                0
-            };
+                _ => 0,
+            }
+        } else {
+            0
+        };
 
-        if let Some((idx, next_char)) = next.peek() {
-            let _idx = *idx;
+        if let Some(&(idx, next_char)) = next.peek() {
             match next_char {
-                ':' | '}' if in_object_key => {
+                ':' | '}' | '=' | ',' if in_object_key => {
                     // We're ready to close the key
                     log::debug!("Closing due to: key");
                     true
                 }
+                '-' if in_object_key => {
+                    // `"key"->value`
+                    next.next();
+                    matches!(next.peek(), Some((_, '>')))
+                }
                 ',' if in_object_value || in_array => {
-                    if closing_char_count % 2 == 0 {
-                        // We're ready to close the value
+                    // Only close the value if the quotes seen so far are balanced
+                    let is_balanced = closing_char_count % 2 == 0;
+                    if is_balanced {
                         log::debug!("Closing due to: value",);
-                        true
+                    }
+                    // With quotes inside the string either choice may be wrong:
+                    // `"say "hi", she said"` vs `"say "hi, there", "b": 1`
+                    if closing_char_count > 0 {
+                        self.decide(idx, is_balanced)
                     } else {
-                        // We're not ready to close the value
-                        false
+                        is_balanced
                     }
                 }
                 '}' if in_object_value => {
@@ -361,13 +591,21 @@ impl JsonParseState {
                     log::debug!("Closing due to: array");
                     true
                 }
+                // A mismatched closer: `["a"}`
+                '}' | ']' if in_array || in_object_value => self.closes_open_collection(next_char),
                 ' ' | '\t' | '\n' => {
                     // look ahead and see if we can find a closing bracket or comma
                     while let Some((_, c)) = next.next() {
                         match c {
                             ' ' | '\t' | '\n' => {}
                             '}' if in_object_key || in_object_value => return true,
-                            ':' if in_object_key => return true,
+                            ':' | '=' | ',' if in_object_key => return true,
+                            // `"key" -> value` or `"key" -1`
+                            '-' if in_object_key => {
+                                return matches!(next.peek(), Some((_, '>' | '0'..='9')))
+                            }
+                            // The colon is missing: `"key" "value"`, `"key" 1`
+                            '"' | '\'' | '{' | '[' | '0'..='9' if in_object_key => return true,
                             ',' if in_object_value => return true,
                             ',' | ']' if in_array => return true,
                             '/' => {
@@ -390,18 +628,12 @@ impl JsonParseState {
                     // If we faile, terminate the string
                     true
                 }
-                x if closing_char == *x => {
+                x if closing_char == x => {
                     // We'll close the string the next time around.
                     false
                 }
-                '{' | '"' | '\'' | '[' => {
-                    if !has_some_object {
-                        // We're in a string
-                        true
-                    } else {
-                        false
-                    }
-                }
+                // Outside of any object we're in a string
+                '{' | '"' | '\'' | '[' => !has_some_object,
                 _ => {
                     // Almost every other character should not close the string
                     false
@@ -412,59 +644,92 @@ impl JsonParseState {
         }
     }
 
-    pub fn process_token(
-        &mut self,
-        token: char,
-        mut next: Peekable<impl Iterator<Item = (usize, char)>>,
-    ) -> Result<usize> {
-        // println!("Processing: {:?}..{:?}", token, next.peek());
+    /// Processes `token`, the character just read from `cursor`. Characters
+    /// that belong to the same piece of syntax are consumed from `cursor` too.
+    pub fn process_token(&mut self, token: char, cursor: &mut Cursor) -> Result<()> {
+        // println!("Processing: {:?}..{:?}", token, cursor.peek());
         match self.collection_stack.last() {
             Some((last, _)) => match last {
-                JsonCollection::Object(_, _) => {
+                JsonCollection::Object(keys, values) => {
+                    let awaiting_value = keys.len() > values.len();
                     match token {
-                        '}' => {
+                        '\n' => {
+                            self.newline_since_value = true;
+                            Ok(())
+                        }
+                        ':' => {
+                            self.separator_pending = false;
+                            Ok(())
+                        }
+                        // `key = value` or `key => value`
+                        '=' if awaiting_value => {
+                            if cursor.next_if(|c| c == '>').is_some() {
+                                self.replace_separator("=>");
+                            } else {
+                                self.replace_separator("=");
+                            }
+                            Ok(())
+                        }
+                        // `key -> value`
+                        '-' if awaiting_value && cursor.peek() == Some('>') => {
+                            cursor.next();
+                            self.replace_separator("->");
+                            Ok(())
+                        }
+                        '}' | ']' => {
                             // We're ready to close the object
-                            self.complete_collection();
-                            Ok(0)
+                            self.close_collection(token, cursor);
+                            Ok(())
+                        }
+                        ',' => {
+                            // `"a": , "b": 2`
+                            if awaiting_value {
+                                self.fill_hole(false);
+                            }
+                            self.comma_since_value = true;
+                            Ok(())
                         }
-                        // We can safely ignore these tokens
-                        ',' | ':' => Ok(0),
                         // look for a new key or value
-                        _ => self.find_any_starting_value(token, next),
+                        _ => self.find_any_starting_value(token, cursor),
                     }
                 }
-                JsonCollection::Array(_) => {
+                JsonCollection::Array(values) => {
                     // We could be expecting:
                     // - A value
                     // - a comma
                     // - a closing bracket
                     match token {
-                        ']' => {
+                        '\n' => {
+                            self.newline_since_value = true;
+                            Ok(())
+                        }
+                        ']' | '}' => {
                             // We're ready to close the array
-                            self.complete_collection();
-                            Ok(0)
+                            self.close_collection(token, cursor);
+                            Ok(())
                         }
                         // Skip these tokens
-                        ',' => Ok(0),
-                        _ => self.find_any_starting_value(token, next),
+                        ',' => {
+                            // `[1,,3]`, but not a leading comma
+                            if self.comma_since_value && !values.is_empty() {
+                                self.fill_hole(false);
+                            }
+                            self.comma_since_value = true;
+                            Ok(())
+                        }
+                        _ => self.find_any_starting_value(token, cursor),
                     }
                 }
                 JsonCollection::TripleQuotedString(_) => {
                     // We should be expecting:
                     if token == '"' {
-                        // TODO: this logic is busted. peekable.peek() does not
-                        // advance the iterator (this is easily verified with
-                        // a unit test), but to fix this we need to do a bit of
-                        // refactoring, so for now we'll live with it.
-                        let is_triple_quoted = match next.peek() {
-                            Some((_, '"')) => matches!(next.peek(), Some((_, '"')) | None),
-                            None => true,
-                            _ => false,
-                        };
-
-                        if is_triple_quoted {
+                        if cursor.starts_with("\"\"") {
+                            cursor.advance(2);
                             self.complete_collection();
-                            Ok(3)
+                            Ok(())
+                        } else if cursor.is_empty() {
+                            self.complete_collection();
+                            Ok(())
                         } else {
                             self.consume(token)
                         }
@@ -480,62 +745,34 @@ impl JsonParseState {
                         '"' => {
                             // It's possible that the LLM messed up the escaping
                             // We'll try to fix it.
-                            if self.should_close_string(next, '"') {
+                            if self.should_close_string(cursor, '"') {
                                 self.complete_collection();
-                                Ok(0)
+                                Ok(())
                             } else {
                                 self.consume(token)
                             }
                         }
                         '\\' => {
                             // Capture escaped characters
-                            match next.peek() {
-                                Some((_, 'n')) => {
-                                    self.consume('\n')?;
-                                    Ok(1)
-                                }
-                                Some((_, 't')) => {
-                                    self.consume('\t')?;
-                                    Ok(1)
-                                }
-                                Some((_, 'r')) => {
-                                    self.consume('\r')?;
-                                    Ok(1)
-                                }
-                                Some((_, 'b')) => {
-                                    self.consume('\x08')?;
-                                    Ok(1)
-                                }
-                                Some((_, 'f')) => {
-                                    self.consume('\x0C')?;
-                                    Ok(1)
-                                }
-                                Some((_, '\\')) => {
-                                    self.consume('\\')?;
-                                    Ok(1)
-                                }
-                                Some((_, '"')) => {
-                                    self.consume('"')?;
-                                    Ok(1)
-                                }
-                                Some((_, 'u')) => {
-                                    // We'll consume the 'u' and the next 4 characters
-                                    let mut buffer = String::new();
-                                    buffer.push(token);
-                                    for _ in 0..4 {
-                                        if let Some((_, c)) = next.next() {
-                                            buffer.push(c);
-                                        } else {
-                                            break;
-                                        }
-                                    }
-                                    for c in buffer.chars() {
-                                        let _ = self.consume(c);
+                            let escaped = match cursor.peek() {
+                                Some('n') => '\n',
+                                Some('t') => '\t',
+                                Some('r') => '\r',
+                                Some('b') => '\x08',
+                                Some('f') => '\x0C',
+                                Some('\\') => '\\',
+                                Some('"') => '"',
+                                Some('u') => match unicode_escape(cursor.rest()) {
+                                    Some((c, len)) => {
+                                        cursor.advance(len);
+                                        return self.consume(c);
                                     }
-                                    Ok(5)
-                                }
-                                _ => self.consume(token),
-                            }
+                                    None => return self.consume(token),
+                                },
+                                _ => return self.consume(token),
+                            };
+                            cursor.next();
+                            self.consume(escaped)
                         }
                         _ => self.consume(token),
                     }
@@ -545,19 +782,13 @@ impl JsonParseState {
                     // - A closing backtick
                     // - A character
                     if token == '`' {
-                        // TODO: this logic is busted. peekable.peek() does not
-                        // advance the iterator (this is easily verified with
-                        // a unit test), but to fix this we need to do a bit of
-                        // refactoring, so for now we'll live with it.
-                        let is_triple_quoted = match next.peek() {
-                            Some((_, '`')) => matches!(next.peek(), Some((_, '`')) | None),
-                            None => true,
-                            _ => false,
-                        };
-
-                        if is_triple_quoted {
+                        if cursor.starts_with("``") {
+                            cursor.advance(2);
                             self.complete_collection();
-                            Ok(3)
+                            Ok(())
+                        } else if cursor.is_empty() {
+                            self.complete_collection();
+                            Ok(())
                         } else {
                             self.consume(token)
                         }
@@ -571,9 +802,9 @@ impl JsonParseState {
                     // - A character
                     match token {
                         '`' => {
-                            if self.should_close_string(next, '`') {
+                            if self.should_close_string(cursor, '`') {
                                 self.complete_collection();
-                                Ok(0)
+                                Ok(())
                             } else {
                                 self.consume(token)
                             }
@@ -590,9 +821,9 @@ impl JsonParseState {
                         '\'' => {
                             // It's possible that the LLM messed up the escaping
                             // We'll try to fix it.
-                            if self.should_close_string(next, '\'') {
+                            if self.should_close_string(cursor, '\'') {
                                 self.complete_collection();
-                                Ok(0)
+                                Ok(())
                             } else {
                                 self.consume(token)
                             }
@@ -605,9 +836,9 @@ impl JsonParseState {
                     // - A terminating json character (comma, colon, bracket, space, newline)
                     // - A character
                     let res = self.consume(token);
-                    if let Some(count) = self.should_close_unescaped_string(next) {
+                    if self.should_close_unescaped_string(cursor) {
                         self.complete_collection();
-                        Ok(count)
+                        Ok(())
                     } else {
                         res
                     }
@@ -620,7 +851,8 @@ impl JsonParseState {
                         '\n' => {
                             // We're ready to close the comment
                             self.complete_collection();
-                            Ok(0)
+                            self.newline_since_value = true;
+                            Ok(())
                         }
                         _ => self.consume(token),
                     }
@@ -632,13 +864,12 @@ impl JsonParseState {
                     match token {
                         '*' => {
                             // We could be closing the comment
-                            match next.peek() {
-                                Some((_, '/')) => {
-                                    // We're ready to close the comment
-                                    self.complete_collection();
-                                    Ok(1)
-                                }
-                                _ => Ok(0),
+                            if cursor.next_if(|c| c == '/').is_some() {
+                                // We're ready to close the comment
+                                self.complete_collection();
+                                Ok(())
+                            } else {
+                                self.consume(token)
                             }
                         }
                         _ => self.consume(token),
@@ -649,18 +880,35 @@ impl JsonParseState {
                 // We could be expecting:
                 // - A value
                 // - Any leading whitespace
-                let preview = next.peekable();
-                self.find_any_starting_value(token, preview)
+                if token == '\n' {
+                    self.newline_since_value = true;
+                }
+                if matches!(token, '}' | ']') {
+                    // `{"a": 1}}`
+                    if let Some((_, _, fixes)) = self.completed_values.last_mut() {
+                        fixes.push(Fixes::DroppedCloser(token));
+                    }
+                    return Ok(());
+                }
+                self.find_any_starting_value(token, cursor)
             }
         }
     }
 
-    // Returns the number of increments to skip after processing the token
-    fn find_any_starting_value(
-        &mut self,
-        token: char,
-        mut next: Peekable<impl Iterator<Item = (usize, char)>>,
-    ) -> Result<usize> {
+    fn find_any_starting_value(&mut self, token: char, cursor: &mut Cursor) -> Result<()> {
+        if self.separator_pending && !token.is_whitespace() && token != '/' {
+            // `"key" value`
+            self.separator_pending = false;
+            self.push_fix(Fixes::InsertedColon);
+        }
+
+        if matches!(token, '{' | '[') && self.collection_stack.len() >= MAX_NESTING {
+            return Err(anyhow::anyhow!(
+                "Nesting deeper than {} levels",
+                MAX_NESTING
+            ));
+        }
+
         match token {
             '{' => {
                 self.collection_stack
@@ -672,18 +920,12 @@ impl JsonParseState {
             }
             '"' => {
                 // Peek if next 2 characters are also quotes
-                let is_triple_quoted = {
-                    next.next_if(|&(_, c)| c == '"')
-                        .and_then(|_| next.next_if(|&(_, c)| c == '"'))
-                        .is_some()
-                };
-
-                if is_triple_quoted {
+                if cursor.starts_with("\"\"") {
+                    cursor.advance(2);
                     self.collection_stack.push((
                         JsonCollection::TripleQuotedString(String::new()),
                         Default::default(),
                     ));
-                    return Ok(2);
                 } else {
                     self.collection_stack.push((
                         JsonCollection::QuotedString(String::new()),
@@ -699,13 +941,8 @@ impl JsonParseState {
             }
             '`' => {
                 // Peek if next 2 characters are also quotes
-                let is_triple_quoted = {
-                    next.next_if(|&(_, c)| c == '`')
-                        .and_then(|_| next.next_if(|&(_, c)| c == '`'))
-                        .is_some()
-                };
-
-                if is_triple_quoted {
+                if cursor.starts_with("``") {
+                    cursor.advance(2);
                     self.collection_stack.push((
                         JsonCollection::TripleBacktickString {
                             lang: None,
@@ -714,7 +951,6 @@ impl JsonParseState {
                         },
                         Default::default(),
                     ));
-                    return Ok(2);
                 } else {
                     self.collection_stack.push((
                         JsonCollection::BacktickString(String::new()),
@@ -724,20 +960,20 @@ impl JsonParseState {
             }
             '/' => {
                 // Could be a comment
-                match next.peek() {
-                    Some((_, '/')) => {
+                match cursor.peek() {
+                    Some('/') => {
+                        cursor.next();
                         self.collection_stack.push((
                             JsonCollection::TrailingComment(String::new()),
                             Default::default(),
                         ));
-                        return Ok(1);
                     }
-                    Some((_, '*')) => {
+                    Some('*') => {
+                        cursor.next();
                         self.collection_stack.push((
                             JsonCollection::BlockComment(String::new()),
                             Default::default(),
                         ));
-                        return Ok(1);
                     }
                     _ => {
                         // if we're in an object, this could be the beginning of a string
@@ -750,7 +986,6 @@ impl JsonParseState {
                                 JsonCollection::UnquotedString(token.into()),
                                 Default::default(),
                             ));
-                            return Ok(0);
                         }
                     }
                 }
@@ -759,13 +994,120 @@ impl JsonParseState {
             x => {
                 self.collection_stack
                     .push((JsonCollection::UnquotedString(x.into()), Default::default()));
-                if let Some(count) = self.should_close_unescaped_string(next) {
+                if self.should_close_unescaped_string(cursor) {
                     self.complete_collection();
-                    return Ok(count);
                 }
             }
         };
 
-        Ok(0)
+        Ok(())
+    }
+}
+
+/// Where an unquoted string or a string closer sits relative to the
+/// collection holding it.
+enum Position {
+    TopLevel,
+    ObjectKey,
+    ObjectValue,
+    ArrayItem,
+    // Inside another string or a comment
+    Other,
+}
+
+/// Decodes a `uXXXX` escape (with its low surrogate if it is a high one) at the
+/// start of `s`, returning the character and how many characters it spans.
+fn unicode_escape(s: &str) -> Option<(char, usize)> {
+    // `from_str_radix` would also accept a sign: `\u+041`
+    let hex = |s: &str| {
+        let digits = s.get(1..5)?;
+        if !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None;
+        }
+        u32::from_str_radix(digits, 16).ok()
+    };
+    let high = hex(s)?;
+    if let Some(c) = char::from_u32(high) {
+        return Some((c, 5));
+    }
+    // `\ud83d\ude00`
+    let low = s.get(5..)?.strip_prefix('\\').and_then(hex)?;
+    if !(0xd800..0xdc00).contains(&high) || !(0xdc00..0xe000).contains(&low) {
+        return None;
+    }
+    let c = char::from_u32(0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00))?;
+    Some((c, 11))
+}
+
+fn closes(collection: &JsonCollection, closer: char) -> bool {
+    closer_of(collection) == Some(closer)
+}
+
+fn closer_of(collection: &JsonCollection) -> Option<char> {
+    match collection {
+        JsonCollection::Object(_, _) => Some('}'),
+        JsonCollection::Array(_) => Some(']'),
+        _ => None,
+    }
+}
+
+fn is_ellipsis(s: &str) -> bool {
+    s == "..." || s == "\u{2026}"
+}
+
+fn is_literal(s: &str) -> bool {
+    s.parse::<f64>().is_ok()
+        || s.eq_ignore_ascii_case("true")
+        || s.eq_ignore_ascii_case("false")
+        || s.eq_ignore_ascii_case("null")
+}
+
+fn opening_bracket(closing: char) -> char {
+    match closing {
+        ')' => '(',
+        ']' => '[',
+        _ => '{',
+    }
+}
+
+/// Whether `rest` starts with something shaped like an object key followed by
+/// a separator: `name:`, `"name" :`, `first name =`, `key ->`.
+fn looks_like_key(rest: impl Iterator<Item = (usize, char)>) -> bool {
+    let mut rest = rest
+        .map(|(_, c)| c)
+        .skip_while(|c| c.is_whitespace())
+        .peekable();
+
+    match rest.peek().copied() {
+        Some(quote @ ('"' | '\'')) => {
+            rest.next();
+            loop {
+                match rest.next() {
+                    Some(c) if c == quote => break,
+                    Some('\n') | None => return false,
+                    Some(_) => {}
+                }
+            }
+        }
+        Some(c) if c.is_alphabetic() || c == '_' => {
+            let mut len = 0;
+            while rest
+                .next_if(|&c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | ' '))
+                .is_some()
+            {
+                len += 1;
+                if len > 40 {
+                    return false;
+                }
+            }
+        }
+        _ => return false,
+    }
+
+    while rest.next_if(|&c| c == ' ' || c == '\t').is_some() {}
+    match rest.next() {
+        Some(':' | '=') => true,
+        Some('-') => rest.next() == Some('>'),
+        _ => false,
     }
 }
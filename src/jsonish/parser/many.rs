@@ -0,0 +1,280 @@
+use std::ops::Range;
+
+use anyhow::Result;
+
+use crate::jsonish::Value;
+
+use super::{entry, ParseOptions};
+
+/// How [`parse_many_with`] splits its input into documents.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ManyMode {
+    /// Documents written back to back (`{...}{...}`, `{...}\n{...}`), each of
+    /// which may span several lines.
+    #[default]
+    Concatenated,
+    /// One document per line (NDJSON). Every line is parsed on its own, so a
+    /// broken line does not affect the ones after it. Blank lines are skipped.
+    Lines,
+}
+
+/// A document found by [`parse_many_with`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Document {
+    /// Byte range of the document in the input.
+    pub range: Range<usize>,
+    pub value: Value,
+}
+
+/// Parses several JSON documents written back to back from `str`, yielding
+/// each value in order. See [`parse_many_with`] for byte ranges, options and
+/// NDJSON.
+pub fn parse_many(str: &str) -> impl Iterator<Item = Result<Value>> + '_ {
+    parse_many_with(str, ParseOptions::default(), ManyMode::default())
+        .map(|doc| doc.map(|doc| doc.value))
+}
+
+/// Parses several JSON documents from `str`, yielding each in order.
+///
+/// Every document goes through the same lenient pipeline as
+/// [`parse`](super::parse). Prose between concatenated documents is skipped;
+/// a line of NDJSON that is not JSON is an error. Errors carry the byte range
+/// of the document and iteration continues with the next one.
+pub fn parse_many_with(
+    str: &str,
+    options: ParseOptions,
+    mode: ManyMode,
+) -> impl Iterator<Item = Result<Document>> + '_ {
+    let options = ParseOptions {
+        allow_as_string: false,
+        ..options
+    };
+
+    let mut ranges: Box<dyn Iterator<Item = Range<usize>> + '_> = match mode {
+        ManyMode::Concatenated => Box::new(Concatenated { str, offset: 0 }),
+        ManyMode::Lines => Box::new(lines(str)),
+    };
+
+    std::iter::from_fn(move || {
+        let range = ranges.next()?;
+        Some(
+            entry::parse(&str[range.clone()], options)
+                .map(|value| Document {
+                    range: range.clone(),
+                    value,
+                })
+                .map_err(|e| e.context(format!("Failed to parse document at {:?}", range))),
+        )
+    })
+}
+
+/// Ranges of the non-blank lines, without their line break.
+fn lines(str: &str) -> impl Iterator<Item = Range<usize>> + '_ {
+    let mut offset = 0;
+    str.split_inclusive('\n').filter_map(move |line| {
+        let start = offset;
+        offset += line.len();
+        let content = line.trim_end_matches(['\n', '\r']);
+        (!content.trim().is_empty()).then(|| start..start + content.len())
+    })
+}
+
+/// Ranges of documents written back to back.
+struct Concatenated<'a> {
+    str: &'a str,
+    offset: usize,
+}
+
+impl Iterator for Concatenated<'_> {
+    type Item = Range<usize>;
+
+    fn next(&mut self) -> Option<Range<usize>> {
+        loop {
+            let rest = &self.str[self.offset..];
+            let start = self.offset + (rest.len() - rest.trim_start().len());
+            if start >= self.str.len() {
+                return None;
+            }
+            let rest = &self.str[start..];
+            match document_len(rest) {
+                Some(len) => {
+                    self.offset = start + len;
+                    return Some(start..start + len);
+                }
+                // Prose between documents: `Here you go: {...}`
+                None => {
+                    let skip = rest.chars().next().map_or(1, char::len_utf8);
+                    self.offset = start
+                        + rest[skip..]
+                            .find(['{', '['])
+                            .map_or(rest.len(), |i| i + skip);
+                }
+            }
+        }
+    }
+}
+
+/// The length of the document at the start of `str`, or `None` when it
+/// starts with text that is not JSON.
+fn document_len(str: &str) -> Option<usize> {
+    let mut chars = str.char_indices().peekable();
+    let (_, first) = chars.next()?;
+
+    match first {
+        '{' | '[' => {
+            let mut depth = 1;
+            let mut quote = None;
+            let mut escaped = false;
+            // The last character outside strings that was not whitespace
+            let mut prev = first;
+            // Where the document ends if it is never closed
+            let mut split = None;
+            while let Some((idx, c)) = chars.next() {
+                match c {
+                    _ if escaped => escaped = false,
+                    '\\' if quote.is_some() => escaped = true,
+                    _ if quote == Some(c) => quote = None,
+                    _ if quote.is_some() => {}
+                    '"' => quote = Some(c),
+                    // A single quoted string, `{'a': '}'}`, but not the
+                    // apostrophe of `{a: it's}`
+                    '\'' if matches!(prev, '{' | '[' | ',' | ':') => quote = Some(c),
+                    '{' | '[' => depth += 1,
+                    '}' | ']' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            return Some(idx + 1);
+                        }
+                    }
+                    // Should the document be left open: pretty printers
+                    // indent nested values, so an opener at the start of a
+                    // line begins the next document.
+                    '\n' if split.is_none()
+                        && matches!(chars.peek(), Some((_, c)) if *c == first) =>
+                    {
+                        split = Some(idx);
+                    }
+                    _ => {}
+                }
+                if quote.is_none() && !c.is_whitespace() {
+                    prev = c;
+                }
+            }
+            Some(split.unwrap_or(str.len()))
+        }
+        '"' => {
+            let mut escaped = false;
+            for (idx, c) in chars {
+                match c {
+                    _ if escaped => escaped = false,
+                    '\\' => escaped = true,
+                    '"' => return Some(idx + 1),
+                    _ => {}
+                }
+            }
+            Some(str.len())
+        }
+        // A scalar such as `1` or `true`
+        _ => {
+            let len = str
+                .find(|c: char| c.is_whitespace() || matches!(c, '{' | '[' | '"'))
+                .unwrap_or(str.len());
+            serde_json::from_str::<serde_json::Value>(&str[..len])
+                .is_ok()
+                .then_some(len)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jsonish::jsonish_to_serde;
+
+    fn documents(str: &str, mode: ManyMode) -> Vec<Result<(Range<usize>, serde_json::Value)>> {
+        parse_many_with(str, ParseOptions::default(), mode)
+            .map(|doc| doc.map(|doc| (doc.range, jsonish_to_serde(&doc.value))))
+            .collect()
+    }
+
+    #[test]
+    fn test_concatenated_documents() {
+        let input = r#"{"a": "}{"}{"b": [1, 2]} 3 "four"
+{"c": 5"#;
+        let docs = documents(input, ManyMode::Concatenated);
+        let docs: Vec<_> = docs.into_iter().map(Result::unwrap).collect();
+        assert_eq!(
+            docs,
+            vec![
+                (0..11, serde_json::json!({"a": "}{"})),
+                (11..24, serde_json::json!({"b": [1, 2]})),
+                (25..26, serde_json::json!(3)),
+                (27..33, serde_json::json!("four")),
+                (34..41, serde_json::json!({"c": 5})),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_prose_and_single_quotes_between_documents() {
+        let input = "Here are both: {'a': 'b}'} and then\n{'b': \"it's\", c: it's} done.";
+        let docs = parse_many(input)
+            .map(|doc| doc.map(|value| jsonish_to_serde(&value)))
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(
+            docs,
+            vec![
+                serde_json::json!({"a": "b}"}),
+                serde_json::json!({"b": "it's", "c": "it's"}),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_recovers_after_broken_line() {
+        let input = "{\"a\": 1\n{\"b\": 2}\n";
+        let docs = documents(input, ManyMode::Concatenated);
+        let docs: Vec<_> = docs.into_iter().map(Result::unwrap).collect();
+        assert_eq!(
+            docs,
+            vec![
+                (0..7, serde_json::json!({"a": 1})),
+                (8..16, serde_json::json!({"b": 2})),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pretty_printed_documents_stay_whole() {
+        let input = "[\n[1, 2],\n[3, 4]\n]\n{\n  \"a\": {\n    \"b\": 1\n  }\n}";
+        let docs = parse_many(input)
+            .map(|doc| doc.map(|value| jsonish_to_serde(&value)))
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(
+            docs,
+            vec![
+                serde_json::json!([[1, 2], [3, 4]]),
+                serde_json::json!({"a": {"b": 1}}),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ndjson_lines() {
+        let input = "{\"a\": 1}\r\n\nnot json at all\n{\"b\": [2, 3\n";
+        let docs = documents(input, ManyMode::Lines);
+        assert_eq!(docs.len(), 3);
+        assert_eq!(
+            docs[0].as_ref().unwrap(),
+            &(0..8, serde_json::json!({"a": 1}))
+        );
+        let err = docs[1].as_ref().unwrap_err();
+        assert!(err.to_string().contains("11..26"), "{err}");
+        assert_eq!(
+            docs[2].as_ref().unwrap(),
+            &(27..38, serde_json::json!({"b": [2, 3]}))
+        );
+    }
+}
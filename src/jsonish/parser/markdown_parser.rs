@@ -1,5 +1,6 @@
 use crate::jsonish::{
-    parser::{entry, ParsingMode},
+    parser::{csv_parser, entry, yaml_parser, ParsingMode},
+    value::Fixes,
     Value,
 };
 
@@ -18,7 +19,7 @@ pub fn parse(str: &str, options: &ParseOptions) -> Result<Vec<MarkdownResult>> {
     let mut remaining = str;
     // Find regex for markdown blocks (```<tag><EOF|newline>)
 
-    let md_tag_start = regex::Regex::new(r"```([a-zA-Z0-9 ]+)(?:\n|$)")
+    let md_tag_start = regex::Regex::new(r"```([a-zA-Z0-9 ]*)(?:\n|$)")
         .map_err(|e| anyhow::Error::from(e).context("Failed to build regex for md-tag-start"))?;
     let md_tag_end = regex::Regex::new(r"```(?:\n|$)")
         .map_err(|e| anyhow::Error::from(e).context("Failed to build regex for md-tag-end"))?;
@@ -40,23 +41,49 @@ pub fn parse(str: &str, options: &ParseOptions) -> Result<Vec<MarkdownResult>> {
 
         log::trace!("Content:\n-----\n{}\n-----\n", md_content);
 
-        let res = entry::parse(
-            md_content,
-            options.next_from_mode(ParsingMode::JsonMarkdown),
-        );
+        let tag = match tag[3..].trim() {
+            "" => "<unspecified>",
+            tag => tag,
+        };
+        let options = options.next_from_mode(ParsingMode::JsonMarkdown);
+        let fixed = |v: Value, fix: Fixes| {
+            Value::AnyOf(
+                vec![Value::FixedJson(v.into(), vec![fix])],
+                md_content.to_string(),
+            )
+        };
+        let res = match tag {
+            "yaml" | "yml" if options.allow_yaml => yaml_parser::parse(md_content, &options, true)
+                .map(|v| fixed(v, Fixes::InterpretedYaml))
+                .or_else(|_| entry::parse(md_content, options)),
+            "csv" | "tsv" if options.allow_tables => {
+                let delimiter = match tag {
+                    "tsv" => '\t',
+                    _ => csv_parser::sniff(md_content).unwrap_or(','),
+                };
+                Ok(fixed(
+                    csv_parser::parse(md_content, delimiter, &options),
+                    Fixes::ConvertedTable,
+                ))
+            }
+            // CSV / TSV in a plain text block. Code, including untagged
+            // blocks, is often comma separated too, so it is not sniffed.
+            "text" | "txt" | "plaintext" => match csv_parser::sniff(md_content) {
+                Some(delimiter) if options.allow_tables && !md_content.starts_with(['{', '[']) => {
+                    Ok(fixed(
+                        csv_parser::parse(md_content, delimiter, &options),
+                        Fixes::ConvertedTable,
+                    ))
+                }
+                _ => entry::parse(md_content, options),
+            },
+            _ => entry::parse(md_content, options),
+        };
 
         match res {
             Ok(v) => {
                 // TODO: Add any more additional strings here.
-                values.push(MarkdownResult::CodeBlock(
-                    if tag.len() > 3 {
-                        tag[3..].trim()
-                    } else {
-                        "<unspecified>"
-                    }
-                    .to_string(),
-                    v,
-                ));
+                values.push(MarkdownResult::CodeBlock(tag.to_string(), v));
             }
             Err(e) => {
                 log::debug!("Error parsing markdown block: Tag: {tag}\n{:?}", e);
@@ -201,4 +228,32 @@ dolor sit amet
 
         Ok(())
     }
+
+    #[test]
+    fn csv_blocks() -> Result<()> {
+        let res = parse(
+            "```csv\nname,age\nAlice,30\n```\n\n```text\nid\tok\n1\ttrue\n```\n\n```python\nf(a, b)\ng(c, d)\n```\n\n```\nname;age\nBo;4\n```\n\n```\n{\"a\": 1, \"b\": 2}\n```\n",
+            &ParseOptions::default(),
+        )?;
+        let tables = res
+            .iter()
+            .filter_map(|res| match res {
+                MarkdownResult::CodeBlock(tag, value) => {
+                    Some((tag.as_str(), crate::jsonish::jsonish_to_serde(value)))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tables,
+            vec![
+                ("csv", serde_json::json!([{"name": "Alice", "age": 30}])),
+                ("text", serde_json::json!([{"id": 1, "ok": true}])),
+                // Untagged blocks are not sniffed: `name;age` is not a table
+                ("<unspecified>", serde_json::json!({"a": 1, "b": 2})),
+            ]
+        );
+
+        Ok(())
+    }
 }
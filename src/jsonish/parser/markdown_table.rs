@@ -0,0 +1,164 @@
+use crate::jsonish::Value;
+
+use super::{entry, ParseOptions, ParsingMode};
+
+/// Finds GitHub-flavoured pipe tables in `str` and reads each into an array
+/// with one object per row, keyed by the header cells.
+///
+/// ```text
+/// | name  | age |
+/// |-------|----:|
+/// | Alice | 30  |
+/// ```
+///
+/// Cells that are JSON scalars (`30`, `true`, `"quoted"`) or bracketed JSON
+/// are parsed; other cells are strings and empty cells are `null`.
+pub fn parse(str: &str, options: &ParseOptions) -> Vec<Value> {
+    let lines = str.lines().collect::<Vec<_>>();
+    let mut tables = vec![];
+
+    let mut idx = 0;
+    while idx + 1 < lines.len() {
+        let header = cells(lines[idx]);
+        let columns = delimiter_columns(lines[idx + 1]);
+        if !lines[idx].contains('|') || columns != Some(header.len()) {
+            idx += 1;
+            continue;
+        }
+
+        let mut rows = vec![];
+        idx += 2;
+        while idx < lines.len() && lines[idx].contains('|') {
+            let mut row = cells(lines[idx]).into_iter();
+            rows.push(Value::Object(
+                header
+                    .iter()
+                    .map(|key| {
+                        let value = row.next().map_or(Value::Null, |c| scalar(&c, options));
+                        (key.clone(), value)
+                    })
+                    .collect(),
+            ));
+            idx += 1;
+        }
+        tables.push(Value::Array(rows));
+    }
+    tables
+}
+
+/// The cells of a row, without the optional outer pipes. `\|` is a literal
+/// pipe.
+fn cells(line: &str) -> Vec<String> {
+    let line = line.trim();
+    let line = line.strip_prefix('|').unwrap_or(line);
+    let line = match line.strip_suffix('|') {
+        Some(rest) if !rest.ends_with('\\') => rest,
+        _ => line,
+    };
+
+    let mut cells = vec![];
+    let mut cell = String::new();
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&'|') => {
+                chars.next();
+                cell.push('|');
+            }
+            '|' => cells.push(std::mem::take(&mut cell).trim().to_string()),
+            c => cell.push(c),
+        }
+    }
+    cells.push(cell.trim().to_string());
+    cells
+}
+
+/// The number of columns of a delimiter row (`|---|:--:|`), or `None` if
+/// `line` is not one.
+fn delimiter_columns(line: &str) -> Option<usize> {
+    if !line.contains('-') {
+        return None;
+    }
+    let cells = cells(line);
+    cells
+        .iter()
+        .all(|c| {
+            let c = c.strip_prefix(':').unwrap_or(c);
+            let c = c.strip_suffix(':').unwrap_or(c);
+            !c.is_empty() && c.chars().all(|c| c == '-')
+        })
+        .then_some(cells.len())
+}
+
+/// A cell or other short piece of markdown: JSON scalars and bracketed JSON
+/// are parsed, code spans are unwrapped and empty text is `null`.
+pub(super) fn scalar(text: &str, options: &ParseOptions) -> Value {
+    let text = text
+        .strip_prefix('`')
+        .and_then(|t| t.strip_suffix('`'))
+        .unwrap_or(text)
+        .trim();
+    if text.is_empty() {
+        return Value::Null;
+    }
+    match serde_json::from_str::<Value>(text) {
+        Ok(Value::Object(_) | Value::Array(_)) | Err(_) => {}
+        Ok(value) => return value,
+    }
+    if text.starts_with(['[', '{']) {
+        let options = options.next_from_mode(ParsingMode::JsonMarkdown);
+        if let Ok(value) = entry::parse(text, options) {
+            return value;
+        }
+    }
+    Value::String(text.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jsonish::jsonish_to_serde;
+
+    fn tables(str: &str) -> Vec<serde_json::Value> {
+        parse(str, &ParseOptions::default())
+            .iter()
+            .map(jsonish_to_serde)
+            .collect()
+    }
+
+    #[test]
+    fn test_pipe_tables() {
+        let input = r#"Here are the users:
+
+| name | age | admin | tags | note |
+|:-----|----:|:-----:|------|------|
+| Alice | 30 | true | `["a", "b"]` | a \| b |
+| Bob | 4.5 | no |
+Carol | "007" | null | | |
+
+And the totals:
+
+count | sum
+--- | ---
+2 | 34.5
+"#;
+        assert_eq!(
+            tables(input),
+            vec![
+                serde_json::json!([
+                    {"name": "Alice", "age": 30, "admin": true, "tags": ["a", "b"], "note": "a | b"},
+                    {"name": "Bob", "age": 4.5, "admin": "no", "tags": null, "note": null},
+                    {"name": "Carol", "age": "007", "admin": null, "tags": null, "note": null},
+                ]),
+                serde_json::json!([{"count": 2, "sum": 34.5}]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pipes_without_a_table() {
+        assert!(tables("a | b\nc | d").is_empty());
+        assert!(tables("| a | b |\n|---|\n| 1 | 2 |").is_empty());
+        assert!(tables("x - y\n---").is_empty());
+    }
+}
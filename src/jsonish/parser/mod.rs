@@ -1,9 +1,25 @@
+mod call_parser;
+mod csv_parser;
 mod entry;
 mod fixing_parser;
+mod many;
 mod markdown_parser;
+mod markdown_table;
 mod multi_json_parser;
+mod nested_json;
+mod normalize;
+mod prose_parser;
+mod reasoning;
+mod yaml_parser;
 
 pub use entry::parse;
+pub use many::{parse_many, parse_many_with, Document, ManyMode};
+pub use normalize::{
+    normalize, Normalization, NormalizationKind, NormalizeOptions, NormalizedInput,
+};
+pub use reasoning::{
+    split_reasoning, ReasoningBlock, ReasoningMode, ReasoningOptions, ReasoningSplit,
+};
 
 #[derive(Clone, Copy, Debug)]
 pub struct ParseOptions {
@@ -11,7 +27,17 @@ pub struct ParseOptions {
     allow_markdown_json: bool,
     allow_fixes: bool,
     allow_as_string: bool,
+    allow_yaml: bool,
+    allow_tables: bool,
+    allow_prose: bool,
+    allow_calls: bool,
     depth: usize,
+    nested_json_depth: usize,
+    normalization: Option<NormalizeOptions>,
+    reasoning: Option<ReasoningOptions>,
+    keep_comments: bool,
+    max_hypotheses: usize,
+    hole_policy: HolePolicy,
 }
 
 impl Default for ParseOptions {
@@ -21,11 +47,36 @@ impl Default for ParseOptions {
             allow_markdown_json: true,
             allow_fixes: true,
             allow_as_string: true,
+            allow_yaml: false,
+            allow_tables: true,
+            allow_prose: false,
+            allow_calls: false,
             depth: 0,
+            nested_json_depth: 0,
+            normalization: None,
+            reasoning: None,
+            keep_comments: false,
+            max_hypotheses: 0,
+            hole_policy: HolePolicy::default(),
         }
     }
 }
 
+/// What to put in place of a value that was left out (`{"a": , "b": 2}`,
+/// `[1,,3]`) or elided (`{"a": ...}`). Each hole is also recorded as a
+/// `Fixes::MissingValue` or `Fixes::ElidedValue`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HolePolicy {
+    /// Insert `null`.
+    #[default]
+    Null,
+    /// Leave the member or element out.
+    Drop,
+    /// Insert `null` wrapped in a `Value::FixedJson` carrying the fix, so the
+    /// hole can be told apart from a `null` in the input.
+    Elided,
+}
+
 pub(super) enum ParsingMode {
     JsonMarkdown,
     JsonMarkdownString,
@@ -33,6 +84,89 @@ pub(super) enum ParsingMode {
 }
 
 impl ParseOptions {
+    /// Decode string values whose content is itself JSON (e.g. `"{\"a\": 1}"`),
+    /// repairing it like any other input, following up to `max_depth` levels
+    /// of encoding. `0` disables decoding.
+    pub fn decode_nested_json(mut self, max_depth: usize) -> Self {
+        self.nested_json_depth = max_depth;
+        self
+    }
+
+    /// Rewrite smart quotes, full-width punctuation and invisible characters
+    /// that appear where JSON syntax is expected before parsing.
+    pub fn normalize_input(mut self, normalization: NormalizeOptions) -> Self {
+        self.normalization = Some(normalization);
+        self
+    }
+
+    /// Remove the reasoning of reasoning models (`<think>...</think>`,
+    /// `Reasoning: ...`) before parsing so that JSON written while thinking is
+    /// not mistaken for the answer. Each removed section is recorded as
+    /// `Fixes::StrippedReasoning` on the result.
+    pub fn strip_reasoning(mut self, reasoning: ReasoningOptions) -> Self {
+        self.reasoning = Some(reasoning);
+        self
+    }
+
+    /// Keep `//` and `/* */` comments as `Value::Commented` wrappers on the
+    /// value they trail or precede instead of discarding them.
+    pub fn keep_comments(mut self, keep: bool) -> Self {
+        self.keep_comments = keep;
+        self
+    }
+
+    /// Explore every way of resolving the guesses the fixing parser makes
+    /// (where a string or value ends, e.g. whether `note: Hello, world: 1`
+    /// has one key or two), keeping at most `max` candidates and returning
+    /// them best first. Each candidate costs a run of the fixing parser, so
+    /// this is off (`0`) by default and the input is parsed once.
+    pub fn track_hypotheses(mut self, max: usize) -> Self {
+        self.max_hypotheses = max;
+        self
+    }
+
+    /// Read answers written as YAML (`name: Alice`, `- item`) into values,
+    /// recording `Fixes::InterpretedYaml`. JSON found in the text is still
+    /// preferred, and ```` ```yaml ```` blocks are always read as YAML while
+    /// this is enabled. Off by default, as prose such as `Title: Foo` lines
+    /// reads as YAML too.
+    pub fn interpret_yaml(mut self, allow: bool) -> Self {
+        self.allow_yaml = allow;
+        self
+    }
+
+    /// Read markdown pipe tables and CSV / TSV code blocks into arrays of
+    /// objects keyed by the header row, recording `Fixes::ConvertedTable`. JSON
+    /// found next to a table comes first and the table follows it as a
+    /// candidate. On by default, as a table needs its `|---|` delimiter row.
+    pub fn interpret_tables(mut self, allow: bool) -> Self {
+        self.allow_tables = allow;
+        self
+    }
+
+    /// When nothing else is found, also offer the structure of a prose answer
+    /// (bullet and numbered lists, `**Key:** value` lines) as a candidate
+    /// after the string itself, recording `Fixes::StructuredProse`.
+    pub fn interpret_prose(mut self, allow: bool) -> Self {
+        self.allow_prose = allow;
+        self
+    }
+
+    /// Read tool calls written as code, such as `search(query="rust", limit=5)`
+    /// or `Action: get_weather(city='Paris')` on a line of their own, into
+    /// `{"name": ..., "arguments": {...}}` objects, recording
+    /// `Fixes::InterpretedCall`. Off by default.
+    pub fn interpret_calls(mut self, allow: bool) -> Self {
+        self.allow_calls = allow;
+        self
+    }
+
+    /// How to fill values that are missing or elided, see [`HolePolicy`].
+    pub fn fill_holes(mut self, policy: HolePolicy) -> Self {
+        self.hole_policy = policy;
+        self
+    }
+
     pub(super) fn next_from_mode(&self, curr_mode: ParsingMode) -> Self {
         let mut new = *self;
         match curr_mode {
@@ -18,12 +18,27 @@ pub fn parse(str: &str, options: &ParseOptions) -> Result<Vec<Value>> {
                 stack.push(character);
             }
             '}' | ']' => {
-                if let Some(last) = stack.last() {
-                    let expected_open = if character == '}' { '{' } else { '[' };
-                    if *last == expected_open {
+                let opener = if character == '}' { '{' } else { '[' };
+                match stack.iter().rposition(|&c| c == opener) {
+                    // A stray closer: `{"a": 1], "b": 2}`
+                    None => continue,
+                    Some(i) if i + 1 == stack.len() => {
                         stack.pop();
-                    } else {
-                        return Err(anyhow::anyhow!("Mismatched brackets"));
+                    }
+                    // A closer of the wrong kind, mistyped if more follows
+                    // (`{"a": [1, 2}, "b": 3}`), otherwise the inner collections
+                    // were left open (`{"a": [1, 2} ok`). The fixing parser
+                    // repairs the slice either way.
+                    Some(i) => {
+                        let continues = matches!(
+                            str[index + 1..].trim_start().chars().next(),
+                            Some(',' | '}' | ']')
+                        );
+                        if continues {
+                            stack.pop();
+                        } else {
+                            stack.truncate(i);
+                        }
                     }
                 }
 
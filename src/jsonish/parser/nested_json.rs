@@ -0,0 +1,183 @@
+use crate::jsonish::{jsonish_to_serde, value::Fixes, Value};
+
+use super::{entry, ParseOptions};
+
+/// Replaces string values that contain a JSON document (a document that was
+/// serialized into a string, often an `arguments` field) with
+/// `AnyOf([decoded, raw])`.
+///
+/// Decoded values are searched again until `remaining` levels of encoding have
+/// been undone. A string that only held a quoted string (`"\"Hamlet\""`) may
+/// have been meant literally, so its raw form stays the first candidate.
+pub fn decode(value: Value, options: &ParseOptions, remaining: usize) -> Value {
+    if remaining == 0 {
+        return value;
+    }
+
+    match value {
+        Value::String(s) => match decode_str(&s, options) {
+            Some(decoded) => {
+                let decoded = decode(decoded, options, remaining - 1);
+                let ambiguous = matches!(decoded, Value::String(_));
+                let decoded = Value::FixedJson(Box::new(decoded), vec![Fixes::DecodedJsonString]);
+                let raw = Value::String(s.clone());
+                let candidates = if ambiguous {
+                    vec![raw, decoded]
+                } else {
+                    vec![decoded, raw]
+                };
+                Value::AnyOf(candidates, s)
+            }
+            None => Value::String(s),
+        },
+        Value::Object(fields) => Value::Object(
+            fields
+                .into_iter()
+                .map(|(k, v)| (k, decode(v, options, remaining)))
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(
+            items
+                .into_iter()
+                .map(|v| decode(v, options, remaining))
+                .collect(),
+        ),
+        Value::Markdown(tag, inner) => {
+            Value::Markdown(tag, Box::new(decode(*inner, options, remaining)))
+        }
+        Value::FixedJson(inner, fixes) => {
+            Value::FixedJson(Box::new(decode(*inner, options, remaining)), fixes)
+        }
+        Value::AnyOf(items, original) => Value::AnyOf(
+            items
+                .into_iter()
+                .map(|v| decode(v, options, remaining))
+                .collect(),
+            original,
+        ),
+        Value::Commented(inner, comments) => {
+            Value::Commented(Box::new(decode(*inner, options, remaining)), comments)
+        }
+        v @ (Value::Number(_) | Value::Boolean(_) | Value::Null) => v,
+    }
+}
+
+/// The value encoded in `s`, when `s` holds a JSON string literal or a
+/// bracketed document. Documents that are not strict JSON are read with
+/// [`entry::parse`] and kept when they yield an object or array with at least
+/// one key or item separator, so templates such as `{name}` stay strings.
+fn decode_str(s: &str, options: &ParseOptions) -> Option<Value> {
+    let trimmed = s.trim();
+
+    // Only bracketed content and string literals are considered: prose that
+    // merely starts with a `[` (e.g. "[note] ...") should stay a string.
+    let looks_like_json = (trimmed.starts_with('{') && trimmed.ends_with('}'))
+        || (trimmed.starts_with('[') && trimmed.ends_with(']'))
+        || (trimmed.len() >= 2 && trimmed.starts_with('"') && trimmed.ends_with('"'));
+    if !looks_like_json {
+        return None;
+    }
+
+    match serde_json::from_str::<Value>(trimmed) {
+        Ok(v) => return Some(v),
+        Err(e) => {
+            log::debug!("Nested string is not strict JSON: {:?}", e);
+        }
+    }
+    if trimmed.starts_with('"') || !trimmed.contains([':', ',']) {
+        return None;
+    }
+
+    let mut options = *options;
+    options.allow_as_string = false;
+    // Nested levels are handled by `decode` itself, not by the top-level pass.
+    options.depth += 1;
+    match entry::parse(trimmed, options) {
+        Ok(v) if jsonish_to_serde(&v).is_object() || jsonish_to_serde(&v).is_array() => Some(v),
+        Ok(v) => {
+            log::debug!("Nested string is not a document: {:?}", v);
+            None
+        }
+        Err(e) => {
+            log::debug!("Failed to decode nested JSON string: {:?}", e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jsonish::jsonish_to_serde;
+
+    fn decoded(value: serde_json::Value, depth: usize) -> serde_json::Value {
+        let value = serde_json::from_value::<Value>(value).unwrap();
+        jsonish_to_serde(&decode(value, &ParseOptions::default(), depth))
+    }
+
+    #[test]
+    fn test_decodes_documents() {
+        assert_eq!(
+            decoded(
+                serde_json::json!({"arguments": "{\"q\": \"rust\", \"ids\": \"[1, 2]\"}"}),
+                2
+            ),
+            serde_json::json!({"arguments": {"q": "rust", "ids": [1, 2]}})
+        );
+        // A document encoded twice: the outer string holds a string literal
+        assert_eq!(
+            decoded(serde_json::json!("\"{\\\"a\\\": 1}\""), 2),
+            serde_json::json!({"a": 1})
+        );
+    }
+
+    #[test]
+    fn test_decodes_jsonish_documents() {
+        assert_eq!(
+            decoded(
+                serde_json::json!({"arguments": "{\"q\": \"rust\", \"ids\": [1, 2,],}"}),
+                2
+            ),
+            serde_json::json!({"arguments": {"q": "rust", "ids": [1, 2]}})
+        );
+        assert_eq!(
+            decoded(serde_json::json!({"t": "{\"a\": 1,}"}), 2),
+            serde_json::json!({"t": {"a": 1}})
+        );
+        assert_eq!(
+            decoded(serde_json::json!({"t": "{q: 'rust'}"}), 2),
+            serde_json::json!({"t": {"q": "rust"}})
+        );
+    }
+
+    #[test]
+    fn test_leaves_text_alone() {
+        for text in ["{name}", "[note] see below", "[1] and [2]", "Hamlet"] {
+            assert_eq!(
+                decoded(serde_json::json!({"t": text}), 2),
+                serde_json::json!({"t": text}),
+                "{text}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_quoted_strings_stay_first() {
+        let value = decode(
+            Value::String("\"Hamlet\"".to_string()),
+            &ParseOptions::default(),
+            2,
+        );
+        let Value::AnyOf(candidates, _) = &value else {
+            panic!("Expected AnyOf, got {value:?}");
+        };
+        assert_eq!(candidates[0], Value::String("\"Hamlet\"".to_string()));
+        assert_eq!(
+            candidates[1],
+            Value::FixedJson(
+                Box::new(Value::String("Hamlet".to_string())),
+                vec![Fixes::DecodedJsonString]
+            )
+        );
+    }
+}
@@ -0,0 +1,334 @@
+use std::ops::Range;
+
+/// Which classes of characters the normalization pre-pass rewrites.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NormalizeOptions {
+    /// `“ ” „ ‟` to `"` and `‘ ’` to `'` when used as string delimiters.
+    pub smart_quotes: bool,
+    /// Full-width `： ， ｛ ｝ ［ ］` and friends to their ASCII forms.
+    pub full_width_punctuation: bool,
+    /// Non-breaking, ideographic and other unicode spaces to `' '`.
+    pub unicode_spaces: bool,
+    /// Zero-width spaces and joiners are removed.
+    pub zero_width: bool,
+    /// A leading byte order mark is removed.
+    pub byte_order_mark: bool,
+}
+
+impl Default for NormalizeOptions {
+    fn default() -> Self {
+        Self {
+            smart_quotes: true,
+            full_width_punctuation: true,
+            unicode_spaces: true,
+            zero_width: true,
+            byte_order_mark: true,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NormalizationKind {
+    SmartQuote,
+    FullWidthPunctuation,
+    UnicodeSpace,
+    ZeroWidth,
+    ByteOrderMark,
+    /// An ASCII `"` inside a string delimited by smart quotes.
+    EscapedQuote,
+}
+
+/// A single rewrite performed by [`normalize`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Normalization {
+    /// Byte range in the original input.
+    pub range: Range<usize>,
+    pub replacement: String,
+    pub kind: NormalizationKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NormalizedInput {
+    pub text: String,
+    pub edits: Vec<Normalization>,
+    // For every byte of `text` (plus the end), the byte offset in the input.
+    offsets: Vec<usize>,
+}
+
+impl NormalizedInput {
+    /// Maps a byte offset in `text` back to the original input.
+    pub fn original_offset(&self, offset: usize) -> usize {
+        self.offsets[offset.min(self.offsets.len() - 1)]
+    }
+
+    pub fn kinds(&self) -> Vec<NormalizationKind> {
+        let mut kinds = vec![];
+        for edit in &self.edits {
+            if !kinds.contains(&edit.kind) {
+                kinds.push(edit.kind);
+            }
+        }
+        kinds
+    }
+}
+
+enum StringState {
+    // Outside of any string
+    None,
+    // Inside a `"` delimited string
+    Ascii { escaped: bool },
+    // Inside a `'` delimited string
+    AsciiSingle { escaped: bool },
+    // Inside a string opened by the smart quote `open`, which we rewrote to `"`
+    Smart { open: char, nested: usize },
+    // Inside a string opened by `‘`, which we rewrote to `'`
+    SmartSingle,
+}
+
+/// Whether `c` closes a string opened by the smart quote `open`: `“…”`,
+/// `„…“` (German) or `„…”` (Polish), `”…”` (Swedish).
+fn closes_smart_quote(open: char, c: char) -> bool {
+    match open {
+        '\u{201e}' => matches!(c, '\u{201c}' | '\u{201d}'),
+        _ => c == '\u{201d}',
+    }
+}
+
+/// Rewrites typographic and invisible characters that LLMs and copy-paste
+/// pipelines put where JSON syntax is expected.
+///
+/// Characters inside `"` and `'` delimited strings are legitimate content and
+/// are left untouched (only a leading byte order mark is removed anywhere). A
+/// `'` only opens a string where a value or key can start, so the apostrophe
+/// in `{note: don't}` does not.
+pub fn normalize(input: &str, options: &NormalizeOptions) -> NormalizedInput {
+    let mut text = String::with_capacity(input.len());
+    let mut offsets = Vec::with_capacity(input.len() + 1);
+    let mut edits = vec![];
+
+    let mut state = StringState::None;
+    let mut prev: Option<char> = None;
+    let mut chars = input.char_indices().peekable();
+
+    while let Some((idx, c)) = chars.next() {
+        let next = chars.peek().map(|&(_, c)| c);
+
+        let rewrite: Option<(&str, NormalizationKind)> = match state {
+            _ if idx == 0 && c == '\u{feff}' && options.byte_order_mark => {
+                Some(("", NormalizationKind::ByteOrderMark))
+            }
+            StringState::Ascii { escaped } => {
+                state = match c {
+                    '\\' => StringState::Ascii { escaped: !escaped },
+                    '"' if !escaped => StringState::None,
+                    _ => StringState::Ascii { escaped: false },
+                };
+                None
+            }
+            StringState::AsciiSingle { escaped } => {
+                state = match c {
+                    '\\' => StringState::AsciiSingle { escaped: !escaped },
+                    '\'' if !escaped => StringState::None,
+                    _ => StringState::AsciiSingle { escaped: false },
+                };
+                None
+            }
+            StringState::Smart { open, nested } => match c {
+                // Balanced smart quotes inside the string are content
+                _ if closes_smart_quote(open, c) && nested > 0 => {
+                    state = StringState::Smart {
+                        open,
+                        nested: nested - 1,
+                    };
+                    None
+                }
+                _ if closes_smart_quote(open, c) => {
+                    state = StringState::None;
+                    Some(("\"", NormalizationKind::SmartQuote))
+                }
+                _ if c == open => {
+                    state = StringState::Smart {
+                        open,
+                        nested: nested + 1,
+                    };
+                    None
+                }
+                '"' => Some(("\\\"", NormalizationKind::EscapedQuote)),
+                _ => None,
+            },
+            StringState::SmartSingle => match c {
+                '\u{2019}' if !is_apostrophe(prev, next) => {
+                    state = StringState::None;
+                    Some(("'", NormalizationKind::SmartQuote))
+                }
+                _ => None,
+            },
+            StringState::None => match c {
+                '"' => {
+                    state = StringState::Ascii { escaped: false };
+                    None
+                }
+                '\'' if matches!(
+                    text.trim_end().chars().next_back(),
+                    None | Some('{' | '[' | ',' | ':')
+                ) =>
+                {
+                    state = StringState::AsciiSingle { escaped: false };
+                    None
+                }
+                '\u{201c}' | '\u{201d}' | '\u{201e}' | '\u{201f}' if options.smart_quotes => {
+                    state = StringState::Smart { open: c, nested: 0 };
+                    Some(("\"", NormalizationKind::SmartQuote))
+                }
+                '\u{2018}' | '\u{2019}' if options.smart_quotes && !is_apostrophe(prev, next) => {
+                    state = StringState::SmartSingle;
+                    Some(("'", NormalizationKind::SmartQuote))
+                }
+                _ if options.full_width_punctuation => {
+                    full_width_ascii(c).map(|r| (r, NormalizationKind::FullWidthPunctuation))
+                }
+                _ => None,
+            },
+        };
+
+        // Spaces and invisible characters are only rewritten outside strings.
+        let rewrite = rewrite.or_else(|| match state {
+            StringState::None if options.unicode_spaces && is_unicode_space(c) => {
+                Some((" ", NormalizationKind::UnicodeSpace))
+            }
+            StringState::None if options.zero_width && is_zero_width(c) => {
+                Some(("", NormalizationKind::ZeroWidth))
+            }
+            _ => None,
+        });
+
+        match rewrite {
+            Some((replacement, kind)) => {
+                text.push_str(replacement);
+                offsets.extend(replacement.bytes().map(|_| idx));
+                edits.push(Normalization {
+                    range: idx..idx + c.len_utf8(),
+                    replacement: replacement.to_string(),
+                    kind,
+                });
+            }
+            None => {
+                text.push(c);
+                offsets.extend(idx..idx + c.len_utf8());
+            }
+        }
+        prev = Some(c);
+    }
+    offsets.push(input.len());
+
+    NormalizedInput {
+        text,
+        edits,
+        offsets,
+    }
+}
+
+/// `it’s` is an apostrophe, not a delimiter.
+fn is_apostrophe(prev: Option<char>, next: Option<char>) -> bool {
+    prev.is_some_and(char::is_alphanumeric) && next.is_some_and(char::is_alphanumeric)
+}
+
+fn full_width_ascii(c: char) -> Option<&'static str> {
+    Some(match c {
+        '\u{ff1a}' => ":",
+        '\u{ff0c}' | '\u{3001}' => ",",
+        '\u{ff5b}' => "{",
+        '\u{ff5d}' => "}",
+        '\u{ff3b}' => "[",
+        '\u{ff3d}' => "]",
+        '\u{ff07}' => "'",
+        _ => return None,
+    })
+}
+
+fn is_unicode_space(c: char) -> bool {
+    matches!(
+        c,
+        '\u{a0}' | '\u{2000}'..='\u{200a}' | '\u{202f}' | '\u{205f}' | '\u{3000}'
+    )
+}
+
+fn is_zero_width(c: char) -> bool {
+    matches!(
+        c,
+        '\u{200b}' | '\u{200c}' | '\u{200d}' | '\u{2060}' | '\u{feff}'
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_syntax_is_normalized() {
+        let input = "\u{feff}｛“name”：“Alice”，\u{a0}“tags”：［“a”，“b”］｝";
+        let normalized = normalize(input, &NormalizeOptions::default());
+        assert_eq!(normalized.text, r#"{"name":"Alice", "tags":["a","b"]}"#);
+        assert_eq!(
+            normalized.kinds(),
+            vec![
+                NormalizationKind::ByteOrderMark,
+                NormalizationKind::FullWidthPunctuation,
+                NormalizationKind::SmartQuote,
+                NormalizationKind::UnicodeSpace,
+            ]
+        );
+
+        // `:` maps back to the full-width colon it replaced.
+        let colon = normalized.text.find(':').unwrap();
+        assert_eq!(&input[normalized.original_offset(colon)..][..3], "：");
+        assert_eq!(
+            normalized.original_offset(normalized.text.len()),
+            input.len()
+        );
+    }
+
+    #[test]
+    fn test_string_content_is_preserved() {
+        let input = "{\"quote\": \"she said “hi”，\u{a0}ok\", \"emoji\": \"👩\u{200d}💻\"}";
+        let normalized = normalize(input, &NormalizeOptions::default());
+        assert_eq!(normalized.text, input);
+        assert!(normalized.edits.is_empty());
+
+        let input = "{'quote'：'she said “hi”，\u{a0}it\\'s ok'}";
+        let normalized = normalize(input, &NormalizeOptions::default());
+        assert_eq!(
+            normalized.text,
+            "{'quote':'she said “hi”，\u{a0}it\\'s ok'}"
+        );
+
+        // An apostrophe in an unquoted value does not open a string
+        let normalized = normalize("{note: don't， ok}", &NormalizeOptions::default());
+        assert_eq!(normalized.text, "{note: don't, ok}");
+    }
+
+    #[test]
+    fn test_smart_quoted_string_escapes_ascii_quote() {
+        let input = "{“it’s”: “say \"hi\"”}";
+        let normalized = normalize(input, &NormalizeOptions::default());
+        assert_eq!(normalized.text, r#"{"it’s": "say \"hi\""}"#);
+    }
+
+    #[test]
+    fn test_smart_quotes_pair_with_their_opener() {
+        for (input, expected) in [
+            // German and Polish quotes
+            ("{„name“: „Jörg“， „a“: 1}", r#"{"name": "Jörg", "a": 1}"#),
+            ("{„name”: „Jörg”}", r#"{"name": "Jörg"}"#),
+            ("{“a”: “say “hi”， ok”}", r#"{"a": "say “hi”， ok"}"#),
+            // Content of `‘…’` strings is kept
+            (
+                "{‘note’: ‘it’s ：“fine”，\u{a0}ok’}",
+                "{'note': 'it’s ：“fine”，\u{a0}ok'}",
+            ),
+        ] {
+            let normalized = normalize(input, &NormalizeOptions::default());
+            assert_eq!(normalized.text, expected, "{input}");
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use crate::jsonish::Value;
+
+use super::{markdown_table, ParseOptions};
+
+/// Reads the structure out of a prose answer: bullet (`-`, `*`, `+`) and
+/// numbered (`1.`, `1)`) lists become arrays and `Key: value` lines become
+/// objects, with markdown emphasis stripped.
+///
+/// ```text
+/// **Name:** Alice        {"Name": "Alice",
+/// **Age:** 30      =>     "Age": 30,
+/// **Tags:**               "Tags": ["a", "b"]}
+/// - a
+/// - b
+/// ```
+///
+/// List items under a bold key with no value become that key's array. Lines
+/// that are neither (an introduction, a heading) are skipped. Returns `None`
+/// unless at least two lines carry structure, or if the answer mixes loose
+/// list items with keys.
+pub fn parse(str: &str, options: &ParseOptions) -> Option<Value> {
+    let mut fields: Vec<(String, Value)> = vec![];
+    let mut items = vec![];
+    // The field collecting the list that follows `**Key:**`
+    let mut open_field = None;
+    let mut structured = 0;
+
+    for line in str.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (is_item, text) = match list_item(line) {
+            Some(text) => (true, text),
+            None => (false, line),
+        };
+
+        match key_value(text) {
+            Some((key, "", emphasized)) if emphasized => {
+                open_field = Some(fields.len());
+                fields.push((key, Value::Array(vec![])));
+            }
+            Some((key, value, _)) if !value.is_empty() => {
+                open_field = None;
+                fields.push((key, markdown_table::scalar(value, options)));
+            }
+            _ if is_item => {
+                let item = markdown_table::scalar(&strip_emphasis(text), options);
+                match open_field.and_then(|idx| fields.get_mut(idx)) {
+                    Some((_, Value::Array(values))) => values.push(item),
+                    _ => items.push(item),
+                }
+            }
+            _ => {
+                open_field = None;
+                continue;
+            }
+        }
+        structured += 1;
+    }
+
+    if structured < 2 {
+        return None;
+    }
+    match (fields.is_empty(), items.is_empty()) {
+        (false, true) => Some(Value::Object(
+            fields
+                .into_iter()
+                .map(|(key, value)| match value {
+                    Value::Array(values) if values.is_empty() => (key, Value::Null),
+                    value => (key, value),
+                })
+                .collect(),
+        )),
+        (true, false) => Some(Value::Array(items)),
+        _ => None,
+    }
+}
+
+/// The text of a bullet or numbered list item.
+fn list_item(line: &str) -> Option<&str> {
+    if let Some(rest) = line.strip_prefix(['-', '*', '+']) {
+        return rest.strip_prefix([' ', '\t']).map(str::trim);
+    }
+    let digits = line.len() - line.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    if digits == 0 || digits > 3 {
+        return None;
+    }
+    line[digits..]
+        .strip_prefix(['.', ')'])?
+        .strip_prefix([' ', '\t'])
+        .map(str::trim)
+}
+
+/// Splits `Key: value`, `**Key:** value` or `**Key**: value`, returning
+/// whether the key was emphasized.
+fn key_value(text: &str) -> Option<(String, &str, bool)> {
+    for marker in ["**", "__"] {
+        let Some(rest) = text.strip_prefix(marker) else {
+            continue;
+        };
+        let (key, value) = rest.split_once(marker)?;
+        let (key, value) = match key.strip_suffix(':') {
+            Some(key) => (key, value),
+            None => (key, value.strip_prefix(':')?),
+        };
+        let key = key.trim();
+        return (!key.is_empty()).then(|| (key.to_string(), value.trim(), true));
+    }
+
+    let (key, value) = text.split_once(": ")?;
+    let is_key = !key.is_empty()
+        && key.split_whitespace().count() <= 4
+        && !key.contains(['*', '`', '[', '{', '"']);
+    is_key.then(|| (key.to_string(), value.trim(), false))
+}
+
+/// Removes `**strong**` / `__strong__` markers and `*emphasis*` around the
+/// whole text.
+fn strip_emphasis(text: &str) -> String {
+    let text = text.replace("**", "").replace("__", "");
+    match text
+        .strip_prefix(['*', '_'])
+        .and_then(|t| t.strip_suffix(['*', '_']))
+    {
+        Some(inner) if !inner.is_empty() => inner.to_string(),
+        _ => text,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jsonish::jsonish_to_serde;
+
+    fn prose(str: &str) -> Option<serde_json::Value> {
+        parse(str, &ParseOptions::default()).map(|v| jsonish_to_serde(&v))
+    }
+
+    #[test]
+    fn test_lists_and_keys() {
+        assert_eq!(
+            prose("Here is what I found:\n\n1. **apples**\n2) pears\n* 3"),
+            Some(serde_json::json!(["apples", "pears", 3]))
+        );
+        assert_eq!(
+            prose("## User\n**Name:** Alice\n**Age**: 30\n- **Tags:**\n  - a\n  - `b`\nCity: New York"),
+            Some(serde_json::json!({
+                "Name": "Alice",
+                "Age": 30,
+                "Tags": ["a", "b"],
+                "City": "New York",
+            }))
+        );
+    }
+
+    #[test]
+    fn test_unstructured_prose() {
+        assert_eq!(prose("Note: this is the answer."), None);
+        assert_eq!(prose("I think the answer is:\n\n42"), None);
+        assert_eq!(prose("**Name:** Alice\n- a\n- b"), None);
+    }
+}
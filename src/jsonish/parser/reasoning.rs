@@ -0,0 +1,347 @@
+use std::ops::Range;
+
+use crate::jsonish::tool_calls::object_len;
+
+// Bracketed spans running to the end of the input that are tried as JSON
+const MAX_TRAILING_ATTEMPTS: usize = 16;
+
+/// What happens to JSON written inside reasoning blocks.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ReasoningMode {
+    /// Reasoning is not searched for JSON.
+    #[default]
+    Remove,
+    /// JSON found in the reasoning is offered after every candidate from the
+    /// answer, marked `Fixes::FoundInReasoning`.
+    Isolate,
+}
+
+/// Which reasoning sections the pre-pass recognises.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReasoningOptions {
+    /// Tags enclosing reasoning, matched case-insensitively: `<think>...</think>`.
+    /// Opening tags count at the start of a line, closing tags outside JSON
+    /// strings. A block left open ends before JSON that makes up the rest of
+    /// the input, or else runs to the end of it. A closing tag with no opening
+    /// one ends reasoning that started with the input.
+    pub tags: &'static [&'static str],
+    /// A leading `Reasoning:` / `Thinking:` section. It ends at an `Answer:` /
+    /// `Final answer:` line, otherwise at a line starting with JSON or a code
+    /// block, or before JSON that makes up the rest of the input.
+    pub labels: bool,
+    pub mode: ReasoningMode,
+}
+
+impl Default for ReasoningOptions {
+    fn default() -> Self {
+        Self {
+            tags: &["think", "thinking", "reasoning"],
+            labels: true,
+            mode: ReasoningMode::default(),
+        }
+    }
+}
+
+/// A reasoning section removed by [`split_reasoning`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReasoningBlock {
+    /// Byte range of the section in the input, tags and label included.
+    pub range: Range<usize>,
+    /// The reasoning itself, without tags or label.
+    pub text: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReasoningSplit {
+    /// The input without its reasoning.
+    pub answer: String,
+    pub reasoning: Vec<ReasoningBlock>,
+}
+
+const REASONING_LABELS: [&str; 5] = [
+    "reasoning:",
+    "thinking:",
+    "thoughts:",
+    "thought process:",
+    "chain of thought:",
+];
+const ANSWER_LABELS: [&str; 4] = ["final answer:", "answer:", "response:", "output:"];
+
+/// Separates the reasoning of a reasoning model from its answer.
+pub fn split_reasoning(input: &str, options: &ReasoningOptions) -> ReasoningSplit {
+    // ASCII lowercasing keeps byte offsets
+    let lower = input.to_ascii_lowercase();
+    let mut reasoning = vec![];
+
+    for tag in options.tags {
+        let open = format!("<{}>", tag.to_ascii_lowercase());
+        let close = format!("</{}>", tag.to_ascii_lowercase());
+
+        // `</think>` before any `<think>`: the opening tag was in the prompt
+        let first_open = find_tag(&lower, &open, 0);
+        if let Some(end) = find_tag(&lower, &close, 0) {
+            if !matches!(first_open, Some(open) if open <= end) {
+                reasoning.push(ReasoningBlock {
+                    range: 0..end + close.len(),
+                    text: input[..end].trim().to_string(),
+                });
+            }
+        }
+
+        let mut offset = 0;
+        while let Some(start) = find_tag(&lower, &open, offset) {
+            let content = start + open.len();
+            let (text_end, end) = match find_tag(&lower, &close, content) {
+                Some(idx) => (idx, idx + close.len()),
+                None => {
+                    let end = trailing_json(input, content).unwrap_or(input.len());
+                    (end, end)
+                }
+            };
+            reasoning.push(ReasoningBlock {
+                range: start..end,
+                text: input[content..text_end].trim().to_string(),
+            });
+            offset = end;
+        }
+    }
+
+    if options.labels {
+        if let Some(block) = labelled(input, &lower, &reasoning) {
+            reasoning.push(block);
+        }
+    }
+
+    // Blocks nested in another one are part of it
+    reasoning.sort_by_key(|b| (b.range.start, std::cmp::Reverse(b.range.end)));
+    let mut blocks: Vec<ReasoningBlock> = vec![];
+    for block in reasoning {
+        match blocks.last() {
+            Some(last) if block.range.start < last.range.end => {}
+            _ => blocks.push(block),
+        }
+    }
+
+    let mut answer = String::with_capacity(input.len());
+    let mut offset = 0;
+    for block in &blocks {
+        answer.push_str(&input[offset..block.range.start]);
+        offset = block.range.end;
+    }
+    answer.push_str(&input[offset..]);
+
+    ReasoningSplit {
+        answer: answer.trim().to_string(),
+        reasoning: blocks,
+    }
+}
+
+/// A `Reasoning:` section at the start of the input (after tagged blocks).
+fn labelled(input: &str, lower: &str, tagged: &[ReasoningBlock]) -> Option<ReasoningBlock> {
+    let mut start = 0;
+    while let Some(block) = tagged.iter().find(|b| b.range.start == start) {
+        start = block.range.end;
+    }
+    start += lower[start..].len() - lower[start..].trim_start().len();
+    let label = REASONING_LABELS
+        .iter()
+        .find(|label| lower[start..].starts_with(*label))?;
+    let content = start + label.len();
+
+    let block = |range: Range<usize>, text_end: usize| ReasoningBlock {
+        range,
+        text: input[content..text_end].trim().to_string(),
+    };
+    // Lines after the label's one, with their offsets
+    let lines = || {
+        let first = lower[content..]
+            .find('\n')
+            .map_or(lower.len(), |i| content + i + 1);
+        lower[first..]
+            .split_inclusive('\n')
+            .scan(first, |offset, line| {
+                let start = *offset;
+                *offset += line.len();
+                Some((start, line))
+            })
+    };
+
+    // An answer label wins over JSON examples in the reasoning
+    for (line_start, line) in lines() {
+        let trimmed = line.trim_start();
+        let indent = line.len() - trimmed.len();
+        if let Some(answer) = ANSWER_LABELS.iter().find(|l| trimmed.starts_with(*l)) {
+            return Some(block(start..line_start + indent + answer.len(), line_start));
+        }
+    }
+    for (line_start, line) in lines() {
+        if line.trim_start().starts_with(['{', '[', '`']) {
+            return Some(block(start..line_start, line_start));
+        }
+    }
+    // `Reasoning: simple. {"a": 1}`
+    let end = trailing_json(input, content).unwrap_or(input.len());
+    Some(block(start..end, end))
+}
+
+/// The first occurrence of `tag` at or after `from` that is not inside a
+/// JSON string: opening tags must start a line, closing tags may follow text
+/// on theirs.
+fn find_tag(lower: &str, tag: &str, from: usize) -> Option<usize> {
+    let mut offset = from;
+    while let Some(idx) = lower[offset..].find(tag) {
+        let idx = offset + idx;
+        let line_start = lower[..idx].rfind('\n').map_or(0, |i| i + 1).max(from);
+        let before = &lower[line_start..idx];
+        let counts = if tag.starts_with("</") {
+            !in_string(before)
+        } else {
+            before.trim().is_empty()
+        };
+        if counts {
+            return Some(idx);
+        }
+        offset = idx + tag.len();
+    }
+    None
+}
+
+/// Whether a JSON string is left open at the end of `line`:
+/// `{"text": "use <think>`.
+fn in_string(line: &str) -> bool {
+    let mut open = false;
+    let mut escaped = false;
+    for c in line.chars() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if open => escaped = true,
+            '"' => open = !open,
+            _ => {}
+        }
+    }
+    open
+}
+
+/// Where the JSON that makes up the rest of `input`, from `from` on, starts.
+/// Spans closed before the end are skipped whole, so braces in long reasoning
+/// are read once.
+fn trailing_json(input: &str, from: usize) -> Option<usize> {
+    let end = input.trim_end().len();
+    let mut offset = from;
+    let mut attempts = 0;
+    while let Some(idx) = input.get(offset..end)?.find(['{', '[']) {
+        let start = offset + idx;
+        let span_end = start + object_len(&input[start..end]);
+        if span_end < end {
+            offset = span_end;
+            continue;
+        }
+        if serde_json::from_str::<serde::de::IgnoredAny>(&input[start..end]).is_ok() {
+            return Some(start);
+        }
+        attempts += 1;
+        if attempts >= MAX_TRAILING_ATTEMPTS {
+            return None;
+        }
+        offset = start + 1;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(input: &str) -> (String, Vec<String>) {
+        let split = split_reasoning(input, &ReasoningOptions::default());
+        (
+            split.answer,
+            split.reasoning.into_iter().map(|b| b.text).collect(),
+        )
+    }
+
+    #[test]
+    fn test_tagged_reasoning() {
+        assert_eq!(
+            split("<think>maybe {\"a\": 1}?</think>\n{\"a\": 2}"),
+            (
+                "{\"a\": 2}".to_string(),
+                vec!["maybe {\"a\": 1}?".to_string()]
+            )
+        );
+        assert_eq!(
+            split("{\"a\": 2}\n<Thinking>still going {\"b\""),
+            (
+                "{\"a\": 2}".to_string(),
+                vec!["still going {\"b\"".to_string()]
+            )
+        );
+        assert_eq!(
+            split("the prompt opened it</think>{\"a\": 2}"),
+            (
+                "{\"a\": 2}".to_string(),
+                vec!["the prompt opened it".to_string()]
+            )
+        );
+        assert_eq!(
+            split("<think>a <reasoning>b</reasoning></think>c"),
+            (
+                "c".to_string(),
+                vec!["a <reasoning>b</reasoning>".to_string()]
+            )
+        );
+
+        // An unclosed block full of braces is read once
+        let reasoning = "set {x} to [y], ".repeat(50_000);
+        let (answer, _) = split(&format!("<think>{reasoning}\n{{\"a\": 2}}"));
+        assert_eq!(answer, "{\"a\": 2}");
+    }
+
+    #[test]
+    fn test_tags_in_the_answer() {
+        // Inside a JSON string or after text on the line
+        for input in [
+            "{\"text\": \"use <think> tags\", \"b\": 1}",
+            "{\"text\": \"close with </think>\", \"b\": 1}",
+            "Wrap it in <think> tags: {\"b\": 1}",
+        ] {
+            assert_eq!(split(input), (input.to_string(), vec![]));
+        }
+        // A block left open ends before the answer
+        assert_eq!(
+            split("<think>\nhmm, {\"a\": 1}?\n{\"a\": 2}"),
+            (
+                "{\"a\": 2}".to_string(),
+                vec!["hmm, {\"a\": 1}?".to_string()]
+            )
+        );
+    }
+
+    #[test]
+    fn test_labelled_reasoning() {
+        assert_eq!(
+            split("Reasoning: the user wants {\"a\": 1}\nthen more\nFinal Answer: {\"a\": 2}"),
+            (
+                "{\"a\": 2}".to_string(),
+                vec!["the user wants {\"a\": 1}\nthen more".to_string()]
+            )
+        );
+        assert_eq!(
+            split("Thinking: hmm\n\n```json\n{}\n```"),
+            ("```json\n{}\n```".to_string(), vec!["hmm".to_string()])
+        );
+        assert_eq!(
+            split("The reasoning: is fine\n{}"),
+            ("The reasoning: is fine\n{}".to_string(), vec![])
+        );
+        for input in [
+            "Reasoning: simple.\n{\"a\": 1}",
+            "Reasoning: simple. {\"a\": 1}",
+        ] {
+            assert_eq!(
+                split(input),
+                ("{\"a\": 1}".to_string(), vec!["simple.".to_string()])
+            );
+        }
+    }
+}
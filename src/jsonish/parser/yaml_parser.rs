@@ -0,0 +1,370 @@
+use anyhow::Result;
+
+use crate::jsonish::Value;
+
+use super::{entry, ParseOptions, ParsingMode};
+
+// Deeper documents are rejected: blocks are parsed recursively and would
+// overflow the stack.
+const MAX_NESTING: usize = 512;
+
+#[derive(Clone, Copy)]
+struct Line<'a> {
+    indent: usize,
+    // Without indentation and trailing comment
+    content: &'a str,
+    // Without indentation, for block scalars
+    raw: &'a str,
+}
+
+struct YamlParser<'a, 'o> {
+    lines: Vec<Line<'a>>,
+    pos: usize,
+    // Blocks being parsed, see `MAX_NESTING`
+    depth: usize,
+    options: &'o ParseOptions,
+}
+
+/// Parses the block style subset of YAML that models answer with when they
+/// don't write JSON: mappings, `- ` sequences, scalars, `|` / `>` block
+/// scalars and flow collections (`[a, b]`, `{a: 1}`).
+///
+/// Outside a ```` ```yaml ```` fence (`fenced`), at least two entries are
+/// required so that prose such as `Note: see below` stays a string.
+pub fn parse(str: &str, options: &ParseOptions, fenced: bool) -> Result<Value> {
+    let lines = str
+        .lines()
+        .map(|line| {
+            let raw = line.trim_start();
+            Line {
+                indent: line.len() - raw.len(),
+                content: strip_comment(raw).trim_end(),
+                raw,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let mut parser = YamlParser {
+        lines,
+        pos: 0,
+        depth: 0,
+        options,
+    };
+    parser.skip_blank();
+    if parser.pos < parser.lines.len() && parser.lines[parser.pos].content == "---" {
+        parser.pos += 1;
+        parser.skip_blank();
+    }
+
+    let entries = parser
+        .lines
+        .iter()
+        .filter(|l| is_sequence_item(l.content) || split_key(l.content).is_some())
+        .count();
+    if entries < if fenced { 1 } else { 2 } {
+        return Err(anyhow::anyhow!("Not YAML"));
+    }
+
+    let Some(indent) = parser.peek_indent() else {
+        return Err(anyhow::anyhow!("Empty YAML document"));
+    };
+    let value = parser.block(indent)?;
+
+    parser.skip_blank();
+    match parser.lines.get(parser.pos) {
+        None => Ok(value),
+        Some(line) if line.content == "..." || line.content == "---" => Ok(value),
+        Some(line) => Err(anyhow::anyhow!("Unexpected YAML line: {:?}", line.raw)),
+    }
+}
+
+impl YamlParser<'_, '_> {
+    fn skip_blank(&mut self) {
+        while self
+            .lines
+            .get(self.pos)
+            .is_some_and(|l| l.content.is_empty())
+        {
+            self.pos += 1;
+        }
+    }
+
+    fn peek_indent(&mut self) -> Option<usize> {
+        self.skip_blank();
+        self.lines.get(self.pos).map(|l| l.indent)
+    }
+
+    /// Parses the mapping, sequence or scalar starting at the current line.
+    fn block(&mut self, indent: usize) -> Result<Value> {
+        if self.depth >= MAX_NESTING {
+            return Err(anyhow::anyhow!(
+                "YAML nesting deeper than {} levels",
+                MAX_NESTING
+            ));
+        }
+        self.depth += 1;
+        let content = self.lines[self.pos].content;
+        let value = if is_sequence_item(content) {
+            self.sequence(indent)
+        } else if split_key(content).is_some() {
+            self.mapping(indent)
+        } else {
+            self.pos += 1;
+            let value = self.continued_scalar(content.to_string(), indent);
+            Ok(self.scalar(&value))
+        };
+        self.depth -= 1;
+        value
+    }
+
+    fn sequence(&mut self, indent: usize) -> Result<Value> {
+        let mut items = vec![];
+        while self.peek_indent() == Some(indent) {
+            let Line { content, raw, .. } = self.lines[self.pos];
+            if !is_sequence_item(content) {
+                break;
+            }
+            let item = content[1..].trim_start();
+            if item.is_empty() {
+                self.pos += 1;
+                items.push(self.nested(indent)?);
+            } else {
+                // `- name: Alice` starts a mapping indented past the dash
+                let item_indent = indent + (content.len() - item.len());
+                self.lines[self.pos] = Line {
+                    indent: item_indent,
+                    content: item,
+                    raw: &raw[content.len() - item.len()..],
+                };
+                items.push(self.block(item_indent)?);
+            }
+        }
+        Ok(Value::Array(items))
+    }
+
+    fn mapping(&mut self, indent: usize) -> Result<Value> {
+        let mut fields = vec![];
+        while self.peek_indent() == Some(indent) {
+            let Some((key, rest)) = split_key(self.lines[self.pos].content) else {
+                return Err(anyhow::anyhow!(
+                    "Expected a YAML key: {:?}",
+                    self.lines[self.pos].raw
+                ));
+            };
+            self.pos += 1;
+
+            let value = match rest {
+                "" => match self.peek_indent() {
+                    Some(i) if i > indent => self.nested(indent)?,
+                    // Sequences may sit at the indentation of their key
+                    Some(i) if i == indent && is_sequence_item(self.lines[self.pos].content) => {
+                        self.sequence(indent)?
+                    }
+                    // `Here is the data:` is a heading, not a key
+                    _ if key.contains(' ') => {
+                        return Err(anyhow::anyhow!("Not a YAML key: {:?}", key))
+                    }
+                    _ => Value::Null,
+                },
+                "|" | "|-" | "|+" | ">" | ">-" | ">+" => self.block_scalar(rest, indent),
+                rest => {
+                    let rest = self.continued_scalar(rest.to_string(), indent);
+                    // `"a": 1,` is JSON without its braces
+                    if rest.ends_with(',') {
+                        return Err(anyhow::anyhow!("Not YAML: {:?}", rest));
+                    }
+                    self.scalar(&rest)
+                }
+            };
+            fields.push((key, value));
+        }
+        Ok(Value::Object(fields))
+    }
+
+    /// The block indented under the previous line, or null if there is none.
+    fn nested(&mut self, indent: usize) -> Result<Value> {
+        match self.peek_indent() {
+            Some(i) if i > indent => self.block(i),
+            _ => Ok(Value::Null),
+        }
+    }
+
+    /// Folds the lines of a plain scalar that continue on more indented lines.
+    fn continued_scalar(&mut self, mut value: String, indent: usize) -> String {
+        while self.peek_indent().is_some_and(|i| i > indent) {
+            let content = self.lines[self.pos].content;
+            if is_sequence_item(content) || split_key(content).is_some() {
+                break;
+            }
+            value.push(' ');
+            value.push_str(content);
+            self.pos += 1;
+        }
+        value
+    }
+
+    /// `|` keeps line breaks, `>` folds them into spaces.
+    fn block_scalar(&mut self, style: &str, indent: usize) -> Value {
+        let mut lines = vec![];
+        let mut block_indent = None;
+        while let Some(line) = self.lines.get(self.pos) {
+            if line.raw.is_empty() {
+                lines.push(String::new());
+                self.pos += 1;
+                continue;
+            }
+            if line.indent <= indent {
+                break;
+            }
+            let block_indent = *block_indent.get_or_insert(line.indent);
+            let extra = line.indent.saturating_sub(block_indent);
+            lines.push(format!("{}{}", " ".repeat(extra), line.raw));
+            self.pos += 1;
+        }
+        while lines.last().is_some_and(String::is_empty) {
+            lines.pop();
+        }
+
+        let mut text = if style.starts_with('|') {
+            lines.join("\n")
+        } else {
+            lines.join(" ")
+        };
+        if !style.ends_with('-') {
+            text.push('\n');
+        }
+        Value::String(text)
+    }
+
+    fn scalar(&self, text: &str) -> Value {
+        let text = text.trim();
+        match text {
+            "" | "~" | "null" | "Null" | "NULL" => return Value::Null,
+            "true" | "True" | "TRUE" => return Value::Boolean(true),
+            "false" | "False" | "FALSE" => return Value::Boolean(false),
+            _ => {}
+        }
+        if let Ok(n) = text.parse::<serde_json::Number>() {
+            return Value::Number(n);
+        }
+        if text.starts_with('"') {
+            if let Ok(s) = serde_json::from_str::<String>(text) {
+                return Value::String(s);
+            }
+        }
+        if let Some(s) = text.strip_prefix('\'').and_then(|s| s.strip_suffix('\'')) {
+            return Value::String(s.replace("''", "'"));
+        }
+        if text.starts_with(['[', '{']) {
+            let options = self.options.next_from_mode(ParsingMode::JsonMarkdown);
+            if let Ok(value) = entry::parse(text, options) {
+                return value;
+            }
+        }
+        Value::String(text.to_string())
+    }
+}
+
+fn is_sequence_item(content: &str) -> bool {
+    content == "-" || content.starts_with("- ")
+}
+
+/// Splits `key: value` into its key and the rest of the line.
+fn split_key(content: &str) -> Option<(String, &str)> {
+    if let Some(quote @ ('"' | '\'')) = content.chars().next() {
+        let end = content[1..].find(quote)? + 1;
+        let rest = content[end + 1..].strip_prefix(':')?;
+        if !(rest.is_empty() || rest.starts_with(' ')) {
+            return None;
+        }
+        return Some((content[1..end].to_string(), rest.trim()));
+    }
+
+    let (key, rest) = match content.find(": ") {
+        Some(idx) => (&content[..idx], &content[idx + 2..]),
+        None => (content.strip_suffix(':')?, ""),
+    };
+    let is_plain_key = !key.is_empty()
+        && key.len() <= 60
+        && !key.starts_with(['-', ' ', '?'])
+        && key
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | ' ' | '$' | '@' | '/'));
+    is_plain_key.then(|| (key.to_string(), rest.trim()))
+}
+
+/// Removes a `# comment`, which must follow whitespace and sit outside quotes.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut prev = ' ';
+    for (idx, c) in line.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') if prev == ' ' || prev == '-' || prev == ':' => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, '#') if prev.is_whitespace() => return &line[..idx],
+            _ => {}
+        }
+        prev = c;
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jsonish::jsonish_to_serde;
+
+    fn yaml(str: &str) -> Result<serde_json::Value> {
+        parse(str, &ParseOptions::default(), false).map(|v| jsonish_to_serde(&v))
+    }
+
+    #[test]
+    fn test_mappings_and_sequences() {
+        let input = r#"
+name: Alice   # the user
+age: 30
+zip: 00501
+tags:
+  - a
+  - 'b: c'
+friends:
+- name: Bob
+  langs: [rust, "go"]
+- name: Eve
+  bio: Likes long
+    walks.
+note: |
+  line one
+    indented
+empty:
+"#;
+        assert_eq!(
+            yaml(input).unwrap(),
+            serde_json::json!({
+                "name": "Alice",
+                "age": 30,
+                "zip": "00501",
+                "tags": ["a", "b: c"],
+                "friends": [
+                    {"name": "Bob", "langs": ["rust", "go"]},
+                    {"name": "Eve", "bio": "Likes long walks."},
+                ],
+                "note": "line one\n  indented\n",
+                "empty": null,
+            })
+        );
+    }
+
+    #[test]
+    fn test_prose_is_not_yaml() {
+        for input in [
+            "Note: see below",
+            "Here is the data:\nThe user is Alice.",
+            "\"a\": 1,\n\"b\": 2",
+            "{\"a\": 1,\n\"b\": 2",
+            "Sure! Here's what I found:\n- one\n- two",
+        ] {
+            assert!(yaml(input).is_err(), "{input}");
+        }
+    }
+}
@@ -0,0 +1,270 @@
+use anyhow::Result;
+use serde_json::Value as Json;
+
+use crate::jsonish::{parse, ParseOptions, Value};
+
+/// The API a response body came from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Provider {
+    /// Chat Completions (`choices[].message` / `choices[].delta`) and
+    /// Responses (`output[].content[]`, `response.output_text.delta`).
+    OpenAi,
+    /// Messages (`content[]`, `content_block_delta` events).
+    Anthropic,
+    /// Gemini `generateContent` (`candidates[].content.parts[]`).
+    Gemini,
+}
+
+/// The text of a provider response and the metadata that describes how it
+/// ended.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProviderResponse {
+    pub provider: Provider,
+    /// The text parts of the first choice / candidate, concatenated.
+    pub text: String,
+    pub model: Option<String>,
+    /// As reported by the provider (`stop`, `length`, `end_turn`,
+    /// `max_tokens`, `MAX_TOKENS`, ...). `None` when a stream was cut before
+    /// its last event.
+    pub finish_reason: Option<String>,
+}
+
+impl ProviderResponse {
+    /// Whether the text stops early: the output hit a token limit or the
+    /// stream ended without a finish reason.
+    pub fn truncated(&self) -> bool {
+        match self.finish_reason.as_deref() {
+            None => true,
+            Some(reason) => matches!(
+                reason,
+                "length" | "max_tokens" | "max_output_tokens" | "MAX_TOKENS"
+            ),
+        }
+    }
+}
+
+/// Reads the text out of a raw provider response body.
+///
+/// `body` is either a complete response (one JSON object), the accumulated
+/// body of a server-sent event stream (`data: {...}` lines), or a JSON array
+/// of stream chunks as returned by Gemini's `streamGenerateContent`.
+pub fn read_response(body: &str) -> Result<ProviderResponse> {
+    let body = body.trim_start_matches('\u{feff}').trim();
+    let events = match serde_json::from_str::<Json>(body) {
+        Ok(Json::Array(chunks)) => chunks,
+        Ok(response) => vec![response],
+        Err(_) => sse_events(body),
+    };
+
+    let mut response = None;
+    for event in &events {
+        add_event(&mut response, event);
+    }
+    response.ok_or_else(|| anyhow::anyhow!("Not a known provider response"))
+}
+
+/// Adds a response or stream event to `response`, starting it with the first
+/// event that comes from a known provider.
+pub(crate) fn add_event(response: &mut Option<ProviderResponse>, event: &Json) {
+    let Some(provider) = provider_of(event) else {
+        return;
+    };
+    let response = response.get_or_insert_with(|| ProviderResponse {
+        provider,
+        text: String::new(),
+        model: None,
+        finish_reason: None,
+    });
+    match provider {
+        Provider::OpenAi => add_openai(response, event),
+        Provider::Anthropic => add_anthropic(response, event),
+        Provider::Gemini => add_gemini(response, event),
+    }
+}
+
+/// Reads the text out of a provider response body and parses it.
+pub fn parse_response(body: &str, options: ParseOptions) -> Result<(ProviderResponse, Value)> {
+    let response = read_response(body)?;
+    let value = parse(&response.text, options)?;
+    Ok((response, value))
+}
+
+/// The JSON payloads of `data:` lines.
+fn sse_events(body: &str) -> Vec<Json> {
+    let mut sse = SseEvents::default();
+    body.lines()
+        .chain(std::iter::once(""))
+        .filter_map(|line| sse.line(line))
+        .collect()
+}
+
+/// Collects the `data:` lines of server-sent events, one line at a time.
+/// Events split over several `data:` lines are joined, and `[DONE]` and
+/// other payloads that are not JSON are skipped.
+#[derive(Debug, Default)]
+pub(crate) struct SseEvents {
+    data: String,
+}
+
+impl SseEvents {
+    /// Takes a line without its line break, returning the event it ends.
+    pub(crate) fn line(&mut self, line: &str) -> Option<Json> {
+        let line = line.trim_end_matches('\r');
+        if let Some(rest) = line.strip_prefix("data:") {
+            if !self.data.is_empty() {
+                self.data.push('\n');
+            }
+            self.data.push_str(rest.strip_prefix(' ').unwrap_or(rest));
+            return None;
+        }
+        if !line.is_empty() || self.data.is_empty() {
+            return None;
+        }
+
+        let data = std::mem::take(&mut self.data);
+        match serde_json::from_str(&data) {
+            Ok(event) => Some(event),
+            Err(e) => {
+                if data.trim() != "[DONE]" {
+                    log::debug!("Skipping event that is not JSON: {:?}", e);
+                }
+                None
+            }
+        }
+    }
+}
+
+fn provider_of(event: &Json) -> Option<Provider> {
+    let event_type = event["type"].as_str().unwrap_or_default();
+    if event.get("choices").is_some()
+        || event.get("output").is_some()
+        || event_type.starts_with("response.")
+    {
+        Some(Provider::OpenAi)
+    } else if event.get("candidates").is_some() {
+        Some(Provider::Gemini)
+    } else if event_type == "message"
+        || event_type.starts_with("message_")
+        || event_type.starts_with("content_block_")
+    {
+        Some(Provider::Anthropic)
+    } else {
+        None
+    }
+}
+
+fn set_model(response: &mut ProviderResponse, model: &Json) {
+    if let Some(model) = model.as_str() {
+        response.model.get_or_insert_with(|| model.to_string());
+    }
+}
+
+fn set_finish_reason(response: &mut ProviderResponse, reason: &Json) {
+    if let Some(reason) = reason.as_str() {
+        response.finish_reason = Some(reason.to_string());
+    }
+}
+
+/// Appends the `text` of a part that is not a thought.
+fn push_part(text: &mut String, part: &Json) {
+    if part["thought"].as_bool() != Some(true) {
+        if let Some(s) = part["text"].as_str() {
+            text.push_str(s);
+        }
+    }
+}
+
+/// Appends content given as a string or as a list of parts.
+fn push_parts(text: &mut String, parts: &Json) {
+    match parts {
+        Json::String(s) => text.push_str(s),
+        Json::Array(parts) => parts.iter().for_each(|part| push_part(text, part)),
+        _ => {}
+    }
+}
+
+fn add_openai(response: &mut ProviderResponse, event: &Json) {
+    set_model(response, &event["model"]);
+
+    // Chat Completions
+    if let Some(choices) = event["choices"].as_array() {
+        let first = choices
+            .iter()
+            .find(|c| c["index"].as_u64().unwrap_or(0) == 0);
+        if let Some(choice) = first {
+            push_parts(&mut response.text, &choice["message"]["content"]);
+            push_parts(&mut response.text, &choice["delta"]["content"]);
+            set_finish_reason(response, &choice["finish_reason"]);
+        }
+        return;
+    }
+
+    // Responses: a complete response, or the events of its stream
+    let event_type = event["type"].as_str().unwrap_or_default();
+    let (response_body, is_event) = match event_type {
+        "response.output_text.delta" => {
+            if let Some(delta) = event["delta"].as_str() {
+                response.text.push_str(delta);
+            }
+            return;
+        }
+        "response.completed" | "response.incomplete" | "response.failed" => {
+            (&event["response"], true)
+        }
+        _ if event.get("output").is_some() => (event, false),
+        _ => return,
+    };
+    set_model(response, &response_body["model"]);
+    if !is_event {
+        for item in response_body["output"].as_array().into_iter().flatten() {
+            for part in item["content"].as_array().into_iter().flatten() {
+                if part["type"] == "output_text" {
+                    push_part(&mut response.text, part);
+                }
+            }
+        }
+    }
+    match response_body["status"].as_str() {
+        Some("incomplete") => {
+            set_finish_reason(response, &response_body["incomplete_details"]["reason"])
+        }
+        Some(status) => response.finish_reason = Some(status.to_string()),
+        None => {}
+    }
+}
+
+fn add_anthropic(response: &mut ProviderResponse, event: &Json) {
+    match event["type"].as_str().unwrap_or_default() {
+        "message" => {
+            set_model(response, &event["model"]);
+            for block in event["content"].as_array().into_iter().flatten() {
+                if block["type"] == "text" {
+                    push_part(&mut response.text, block);
+                }
+            }
+            set_finish_reason(response, &event["stop_reason"]);
+        }
+        "message_start" => set_model(response, &event["message"]["model"]),
+        "content_block_start" if event["content_block"]["type"] == "text" => {
+            push_parts(&mut response.text, &event["content_block"]["text"]);
+        }
+        "content_block_delta" if event["delta"]["type"] == "text_delta" => {
+            push_parts(&mut response.text, &event["delta"]["text"]);
+        }
+        "message_delta" => set_finish_reason(response, &event["delta"]["stop_reason"]),
+        _ => {}
+    }
+}
+
+fn add_gemini(response: &mut ProviderResponse, event: &Json) {
+    set_model(response, &event["modelVersion"]);
+    let first = event["candidates"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .find(|c| c["index"].as_u64().unwrap_or(0) == 0);
+    if let Some(candidate) = first {
+        push_parts(&mut response.text, &candidate["content"]["parts"]);
+        set_finish_reason(response, &candidate["finishReason"]);
+    }
+}
@@ -0,0 +1,736 @@
+use std::ops::Range;
+
+use anyhow::Result;
+
+use crate::jsonish::{
+    jsonish_to_serde, parse, to_json_string, to_json_string_pretty, tool_calls::object_len,
+    ParseOptions, Value,
+};
+
+/// How many places in the input are tried as the start of the document.
+/// Markdown links (`[text](url)`) are not counted.
+const MAX_REGIONS: usize = 64;
+
+/// What a [`TextEdit`] repairs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EditKind {
+    /// Prose, code fences or other text around the document was removed.
+    RemovedText,
+    /// A `//` or `/* */` comment was removed.
+    RemovedComment,
+    /// An unquoted key or string value was quoted.
+    QuotedString,
+    /// A `'` or backtick quote was replaced with `"`.
+    ReplacedQuote,
+    /// A quote or control character inside a string was escaped, or an
+    /// escape JSON does not have (`\'`) was undone.
+    EscapedCharacter,
+    /// A quote was added to close a string left open.
+    ClosedString,
+    InsertedComma,
+    /// A leading, trailing or doubled comma was removed.
+    RemovedComma,
+    /// A missing `:` was inserted, or `=` was replaced with one.
+    InsertedColon,
+    /// A closer was added for a collection left open.
+    InsertedCloser,
+    /// A closer of the wrong kind was replaced: `[1, 2}`.
+    ReplacedCloser,
+    /// `null` was put in place of a missing (`[1,,3]`) or elided (`...`)
+    /// value.
+    FilledHole,
+    /// A number was rewritten in JSON syntax: `.5`, `+1`, `01`.
+    RewroteNumber,
+    /// The input could not be repaired in place and was replaced with the
+    /// serialized value.
+    Reserialized,
+}
+
+/// A change to the input, like an LSP `TextEdit` but addressed in bytes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TextEdit {
+    /// Byte range of the input that is replaced; empty for an insertion.
+    pub range: Range<usize>,
+    pub new_text: String,
+    pub kind: EditKind,
+}
+
+impl TextEdit {
+    /// The range as LSP positions in `input`: `(line, character)` pairs,
+    /// zero-based, with characters counted in UTF-16 code units.
+    pub fn lsp_range(&self, input: &str) -> ((u32, u32), (u32, u32)) {
+        (
+            lsp_position(input, self.range.start),
+            lsp_position(input, self.range.end),
+        )
+    }
+}
+
+fn lsp_position(input: &str, offset: usize) -> (u32, u32) {
+    let before = &input[..offset];
+    let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+    let line = before.matches('\n').count();
+    let character = before[line_start..].encode_utf16().count();
+    (line as u32, character as u32)
+}
+
+/// Parses `str` like [`parse`] and lists the edits that turn the input into
+/// JSON equal to the value returned, in order and without overlaps.
+///
+/// The edits keep the formatting, key order and number spelling of the
+/// input: quotes, commas, colons and closers are inserted where missing, and
+/// trailing commas, comments and the prose or code fences around the
+/// document are removed. Valid JSON needs no edits. When the value cannot be
+/// reached this way (e.g. YAML or a table was read, or several documents
+/// were combined), a single `EditKind::Reserialized` edit replaces the input
+/// with the value serialized as with `to_json_string`.
+pub fn parse_with_edits(str: &str, options: ParseOptions) -> Result<(Value, Vec<TextEdit>)> {
+    let value = parse(str, options)?;
+    let expected = jsonish_to_serde(&value);
+    let comments = ends_at_comments(&value);
+
+    if let Some(edits) = regions(str)
+        .into_iter()
+        .find_map(|region| repairs_to(str, region, &expected, comments))
+    {
+        return Ok((value, edits));
+    }
+    let edit = TextEdit {
+        range: 0..str.len(),
+        new_text: to_json_string(&value)?,
+        kind: EditKind::Reserialized,
+    };
+    Ok((value, vec![edit]))
+}
+
+/// Applies edits sorted by position that do not overlap, such as those of
+/// [`parse_with_edits`].
+pub fn apply_edits(str: &str, edits: &[TextEdit]) -> String {
+    let mut out = String::with_capacity(str.len());
+    let mut offset = 0;
+    for edit in edits {
+        out.push_str(&str[offset..edit.range.start]);
+        out.push_str(&edit.new_text);
+        offset = edit.range.end;
+    }
+    out.push_str(&str[offset..]);
+    out
+}
+
+/// Repairs `str` with as few textual edits as possible, see
+/// [`parse_with_edits`]. The result is valid JSON that looks like what the
+/// model wrote.
+pub fn repair_source(str: &str, options: ParseOptions) -> Result<String> {
+    let (_, edits) = parse_with_edits(str, options)?;
+    Ok(apply_edits(str, &edits))
+}
+
+/// Puts `new` in place of the JSON that `parsed` was read from, leaving the
+/// rest of `original` (prose, code fences, tags) untouched.
+///
+/// `parsed` is the result of [`parse`] for `original`, e.g. before it was
+/// normalised or had fields redacted. The region replaced is the last of
+/// the whole input, a code block's content or a bracketed span that reads
+/// as `parsed` and holds no smaller such region. `new` is written compact
+/// when the region was on one line and pretty printed, following the
+/// region's indentation, otherwise.
+pub fn replace_json(original: &str, parsed: &Value, new: &Value) -> Result<String> {
+    let region = json_region(original, parsed)
+        .ok_or_else(|| anyhow::anyhow!("The parsed value was not found in the input"))?;
+
+    let json = if original[region.clone()].contains('\n') {
+        let line_start = original[..region.start]
+            .rfind('\n')
+            .map_or(0, |idx| idx + 1);
+        let indent = &original[line_start..region.start];
+        let indent = if indent.trim().is_empty() { indent } else { "" };
+        to_json_string_pretty(new)?.replace('\n', &format!("\n{indent}"))
+    } else {
+        to_json_string(new)?
+    };
+    Ok(format!(
+        "{}{}{}",
+        &original[..region.start],
+        json,
+        &original[region.end..]
+    ))
+}
+
+/// The byte range of `original` that `parsed` was read from, see
+/// [`replace_json`].
+pub fn json_region(original: &str, parsed: &Value) -> Option<Range<usize>> {
+    let expected = jsonish_to_serde(parsed);
+    let comments = ends_at_comments(parsed);
+    let regions = regions(original);
+    let matches = regions
+        .iter()
+        .filter(|r| repairs_to(&original[..r.end], (*r).clone(), &expected, comments).is_some())
+        .cloned()
+        .collect();
+    if let Some(region) = last_innermost(matches) {
+        return Some(region);
+    }
+    // Values that only `parse` reads, e.g. YAML in a code block
+    let options = ParseOptions::default().interpret_yaml(true);
+    let matches = regions
+        .into_iter()
+        .filter(|r| !original[r.start..].starts_with(['{', '[']))
+        .filter(|region| {
+            parse(&original[region.clone()], options)
+                .is_ok_and(|value| jsonish_to_serde(&value) == expected)
+        })
+        .collect();
+    last_innermost(matches)
+}
+
+/// The last of `regions` that contains none of the others: the whole input
+/// only if nothing inside it matches, and the answer rather than an earlier
+/// copy of it, e.g. an example in `<think>`.
+fn last_innermost(regions: Vec<Range<usize>>) -> Option<Range<usize>> {
+    regions
+        .iter()
+        .filter(|r| {
+            !regions
+                .iter()
+                .any(|o| o != *r && r.start <= o.start && o.end <= r.end)
+        })
+        .max_by_key(|r| r.start)
+        .cloned()
+}
+
+/// Whether `value` was parsed with `keep_comments`, under which a comment
+/// ends an unquoted value (`7 // because`) instead of being read into it.
+fn ends_at_comments(value: &Value) -> bool {
+    !value.comments().is_empty()
+}
+
+/// The edits for `region` when they produce `expected`.
+fn repairs_to(
+    str: &str,
+    region: Range<usize>,
+    expected: &serde_json::Value,
+    comments: bool,
+) -> Option<Vec<TextEdit>> {
+    let edits = repair_region(str, region, comments)?;
+    let repaired = apply_edits(str, &edits);
+    match serde_json::from_str::<serde_json::Value>(&repaired) {
+        Ok(found) if found == *expected => Some(edits),
+        _ => None,
+    }
+}
+
+/// Where the document may be: the whole input, the content of each code
+/// block, then each `{` / `[` up to its closer other than a link's text.
+fn regions(str: &str) -> Vec<Range<usize>> {
+    let mut regions = vec![trimmed(str, 0..str.len())];
+
+    let mut offset = 0;
+    while let Some(idx) = str[offset..].find("```") {
+        let Some(newline) = str[offset + idx..].find('\n') else {
+            break;
+        };
+        let start = offset + idx + newline + 1;
+        let end = str[start..]
+            .find("```")
+            .map_or(str.len(), |idx| start + idx);
+        regions.push(trimmed(str, start..end));
+        offset = (end + 3).min(str.len());
+    }
+
+    let mut offset = 0;
+    while let Some(idx) = str[offset..].find(['{', '[']) {
+        let start = offset + idx;
+        let end = start + object_len(&str[start..]);
+        offset = start + 1;
+        // `[text](url)`
+        if str[start..end].ends_with(']') && str[end..].starts_with('(') {
+            continue;
+        }
+        regions.push(start..end);
+        if regions.len() >= MAX_REGIONS {
+            break;
+        }
+    }
+
+    regions.dedup();
+    regions
+}
+
+fn trimmed(str: &str, range: Range<usize>) -> Range<usize> {
+    let text = &str[range.clone()];
+    let start = range.start + text.len() - text.trim_start().len();
+    start..start + text.trim().len()
+}
+
+/// The edits that turn `region` into a JSON document and remove the text
+/// around it.
+fn repair_region(str: &str, region: Range<usize>, comments: bool) -> Option<Vec<TextEdit>> {
+    let mut repairer = Repairer {
+        src: &str[..region.end],
+        pos: region.start,
+        edits: vec![],
+        depth: 0,
+        comments,
+    };
+    repairer.skip_trivia();
+    if repairer.pos == region.end {
+        return None;
+    }
+    repairer.value()?;
+    repairer.skip_trivia();
+    if repairer.pos < region.end {
+        repairer.delete(repairer.pos..region.end, EditKind::RemovedText);
+    }
+
+    let mut edits = repairer.edits;
+    // Insertions go before an edit starting at the same place
+    edits.sort_by_key(|e| (e.range.start, e.range.end));
+    if !str[..region.start].trim().is_empty() {
+        edits.insert(
+            0,
+            TextEdit {
+                range: 0..region.start,
+                new_text: String::new(),
+                kind: EditKind::RemovedText,
+            },
+        );
+    }
+    if !str[region.end..].trim().is_empty() {
+        edits.push(TextEdit {
+            range: region.end..str.len(),
+            new_text: String::new(),
+            kind: EditKind::RemovedText,
+        });
+    }
+    Some(edits)
+}
+
+/// Walks a document the way the fixing parser reads it, recording the edits
+/// that make it valid JSON.
+struct Repairer<'a> {
+    src: &'a str,
+    pos: usize,
+    edits: Vec<TextEdit>,
+    depth: usize,
+    // Whether a comment ends an unquoted value, see `ends_at_comments`
+    comments: bool,
+}
+
+impl Repairer<'_> {
+    fn peek(&self) -> Option<char> {
+        self.src[self.pos..].chars().next()
+    }
+
+    fn edit(&mut self, range: Range<usize>, text: impl Into<String>, kind: EditKind) {
+        self.edits.push(TextEdit {
+            range,
+            new_text: text.into(),
+            kind,
+        });
+    }
+
+    fn insert(&mut self, at: usize, text: &str, kind: EditKind) {
+        self.edit(at..at, text, kind);
+    }
+
+    fn delete(&mut self, range: Range<usize>, kind: EditKind) {
+        self.edit(range, "", kind);
+    }
+
+    /// Skips whitespace and removes comments.
+    fn skip_trivia(&mut self) {
+        loop {
+            let rest = &self.src[self.pos..];
+            let trimmed = rest.trim_start();
+            self.pos += rest.len() - trimmed.len();
+            let len = if trimmed.starts_with("//") {
+                trimmed.find('\n').unwrap_or(trimmed.len())
+            } else if let Some(comment) = trimmed.strip_prefix("/*") {
+                comment.find("*/").map_or(trimmed.len(), |idx| idx + 4)
+            } else {
+                return;
+            };
+            self.delete(self.pos..self.pos + len, EditKind::RemovedComment);
+            self.pos += len;
+        }
+    }
+
+    fn value(&mut self) -> Option<()> {
+        self.depth += 1;
+        if self.depth > 100 {
+            return None;
+        }
+        match self.peek()? {
+            '{' => self.collection('{', '}'),
+            '[' => self.collection('[', ']'),
+            '"' | '\'' | '`' => self.string(false),
+            _ => self.bare(false),
+        }
+        self.depth -= 1;
+        Some(())
+    }
+
+    /// An object or array; members are separated by exactly one comma and
+    /// the collection is closed by `close`.
+    fn collection(&mut self, open: char, close: char) {
+        let is_object = open == '{';
+        self.pos += 1;
+        let mut members = 0;
+        let mut comma: Option<usize> = None;
+        let mut last_end = self.pos;
+        loop {
+            self.skip_trivia();
+            let closed = match self.peek() {
+                None => {
+                    self.insert(self.pos, &close.to_string(), EditKind::InsertedCloser);
+                    true
+                }
+                Some(c) if c == close => {
+                    self.pos += 1;
+                    true
+                }
+                Some('}' | ']') => {
+                    self.edit(self.pos..self.pos + 1, close, EditKind::ReplacedCloser);
+                    self.pos += 1;
+                    true
+                }
+                Some(',') => {
+                    if members == 0 || comma.is_some() && is_object {
+                        self.delete(self.pos..self.pos + 1, EditKind::RemovedComma);
+                    } else if comma.is_some() {
+                        // `[1,,3]`: the hole is a null
+                        self.insert(self.pos, "null", EditKind::FilledHole);
+                    } else {
+                        comma = Some(self.pos);
+                    }
+                    self.pos += 1;
+                    continue;
+                }
+                Some(_) => false,
+            };
+            if closed {
+                if let Some(comma) = comma {
+                    self.delete(comma..comma + 1, EditKind::RemovedComma);
+                }
+                return;
+            }
+
+            if members > 0 && comma.is_none() {
+                self.insert(last_end, ",", EditKind::InsertedComma);
+            }
+            comma = None;
+            members += 1;
+
+            if is_object && !self.member() {
+                return;
+            }
+            if !is_object && self.value().is_none() {
+                return;
+            }
+            last_end = self.pos;
+        }
+    }
+
+    /// `key: value`. Returns false when nothing could be read.
+    fn member(&mut self) -> bool {
+        match self.peek() {
+            Some('"' | '\'' | '`') => self.string(true),
+            Some(_) => self.bare(true),
+            None => return false,
+        }
+        let key_end = self.pos;
+        self.skip_trivia();
+        match self.peek() {
+            Some(':') => self.pos += 1,
+            Some('=') => {
+                self.edit(self.pos..self.pos + 1, ":", EditKind::InsertedColon);
+                self.pos += 1;
+            }
+            _ => self.insert(key_end, ":", EditKind::InsertedColon),
+        }
+        self.skip_trivia();
+        match self.peek() {
+            None | Some(',' | '}' | ']') => {
+                self.insert(self.pos, "null", EditKind::FilledHole);
+                true
+            }
+            Some(_) => self.value().is_some(),
+        }
+    }
+
+    /// A string quoted with `"`, `'` or a backtick, rewritten with `"`.
+    fn string(&mut self, key: bool) {
+        let start = self.pos;
+        let Some(quote) = self.peek() else {
+            return;
+        };
+        if quote != '"' {
+            self.edit(start..start + 1, "\"", EditKind::ReplacedQuote);
+        }
+        self.pos += 1;
+
+        while let Some(c) = self.peek() {
+            let at = self.pos;
+            self.pos += c.len_utf8();
+            match c {
+                _ if c == quote && closes_string(&self.src[self.pos..], key) => {
+                    if quote != '"' {
+                        self.edit(at..at + 1, "\"", EditKind::ReplacedQuote);
+                    }
+                    return;
+                }
+                '\\' => match self.peek() {
+                    Some(next) if next == quote && quote != '"' => {
+                        // `\'` is not an escape in JSON
+                        self.delete(at..at + 1, EditKind::EscapedCharacter);
+                        self.pos += 1;
+                    }
+                    Some('"' | '\\' | '/' | 'b' | 'f' | 'n' | 'r' | 't') => self.pos += 1,
+                    Some('u')
+                        if self.src[self.pos + 1..]
+                            .chars()
+                            .take(4)
+                            .filter(char::is_ascii_hexdigit)
+                            .count()
+                            == 4 =>
+                    {
+                        self.pos += 5
+                    }
+                    _ => self.insert(at, "\\", EditKind::EscapedCharacter),
+                },
+                '"' => self.insert(at, "\\", EditKind::EscapedCharacter),
+                '\n' => self.edit(at..at + 1, "\\n", EditKind::EscapedCharacter),
+                '\r' => self.edit(at..at + 1, "\\r", EditKind::EscapedCharacter),
+                '\t' => self.edit(at..at + 1, "\\t", EditKind::EscapedCharacter),
+                c if (c as u32) < 0x20 => self.edit(
+                    at..at + 1,
+                    format!("\\u{:04x}", c as u32),
+                    EditKind::EscapedCharacter,
+                ),
+                _ => {}
+            }
+        }
+        self.insert(self.pos, "\"", EditKind::ClosedString);
+    }
+
+    /// A number, literal or unquoted string. Keys end at `:`, values at the
+    /// end of the line or a delimiter.
+    fn bare(&mut self, key: bool) {
+        let start = self.pos;
+        let rest = &self.src[start..];
+        let mut len = rest
+            .find(|c: char| matches!(c, ',' | '}' | ']' | '\n') || key && (c == ':' || c == '='))
+            .unwrap_or(rest.len());
+        // `7 // because`, but not `https://`
+        if let Some(idx) = rest[..len]
+            .match_indices('/')
+            .map(|(idx, _)| idx)
+            .filter(|_| self.comments)
+            .find(|&idx| {
+                rest[idx + 1..].starts_with(['/', '*'])
+                    && (idx == 0 || rest[..idx].ends_with(char::is_whitespace))
+            })
+        {
+            len = idx;
+        }
+        let token = rest[..len].trim_end();
+        if token.is_empty() {
+            if key {
+                self.insert(start, "\"\"", EditKind::QuotedString);
+            } else {
+                self.insert(start, "null", EditKind::FilledHole);
+            }
+            return;
+        }
+        let end = start + token.len();
+        self.pos = end;
+
+        if !key {
+            match token {
+                "true" | "false" | "null" => return,
+                "..." | "…" => return self.edit(start..end, "null", EditKind::FilledHole),
+                _ if serde_json::from_str::<serde_json::Number>(token).is_ok() => return,
+                _ => {}
+            }
+            if let Some(number) = number(token) {
+                return self.edit(start..end, number, EditKind::RewroteNumber);
+            }
+        }
+
+        let quoted = serde_json::to_string(token).unwrap_or_default();
+        if quoted.len() == token.len() + 2 {
+            self.insert(start, "\"", EditKind::QuotedString);
+            self.insert(end, "\"", EditKind::QuotedString);
+        } else {
+            self.edit(start..end, quoted, EditKind::QuotedString);
+        }
+    }
+}
+
+/// Whether a quote followed by `after` ends the string rather than being
+/// part of it: `"say "hi" now"`.
+fn closes_string(after: &str, key: bool) -> bool {
+    let next = after.trim_start_matches([' ', '\t', '\r', '\n']);
+    let spaced = next.len() < after.len();
+    match next.chars().next() {
+        None | Some(',' | '}' | ']' | ':' | '=') => true,
+        Some('/') => next[1..].starts_with(['/', '*']),
+        Some('-') => key,
+        // The colon is missing: `"key" "value"`
+        Some('"' | '\'' | '{' | '[' | '0'..='9') => key && spaced,
+        _ => false,
+    }
+}
+
+/// The JSON spelling of a number the fixing parser accepts: `.5`, `+1`, `01`.
+fn number(token: &str) -> Option<String> {
+    if let Ok(n) = token.parse::<i64>() {
+        return Some(n.to_string());
+    }
+    if let Ok(n) = token.parse::<u64>() {
+        return Some(n.to_string());
+    }
+    let n = token.parse::<f64>().ok()?;
+    serde_json::Number::from_f64(n).map(|n| n.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repair(str: &str) -> String {
+        repair_source(str, ParseOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn test_minimal_edits() {
+        assert_eq!(
+            repair(
+                "{\n  name: 'Alice',\n  \"age\": 30.0 // years\n  \"tags\": [\"a\", \"b\",],\n}"
+            ),
+            "{\n  \"name\": \"Alice\",\n  \"age\": \"30.0 // years\",\n  \"tags\": [\"a\", \"b\"]\n}"
+        );
+        // With `keep_comments` the comment ends the value and is removed
+        let options = ParseOptions::default().keep_comments(true);
+        assert_eq!(
+            repair_source("{\"age\": 30.0 // years\n  \"tags\": []}", options).unwrap(),
+            "{\"age\": 30.0, \n  \"tags\": []}"
+        );
+        assert_eq!(
+            repair("{\"items\": [1, 2, {\"k\": \"v"),
+            "{\"items\": [1, 2, {\"k\": \"v\"}]}"
+        );
+        assert_eq!(
+            repair("{\"ok\": yes, \"n\": .5, \"x\": 1e3, \"y\": ...}"),
+            "{\"ok\": \"yes\", \"n\": 0.5, \"x\": 1e3, \"y\": null}"
+        );
+    }
+
+    #[test]
+    fn test_surrounding_text_is_removed() {
+        assert_eq!(
+            repair("Here you go:\n```json\n{\"a\": 1,}\n```\nAnything else?"),
+            "{\"a\": 1}"
+        );
+        assert_eq!(
+            repair("The result is {\"a\": [1, 2]} as requested."),
+            "{\"a\": [1, 2]}"
+        );
+        // Valid input is returned as is
+        let valid = "  {\"b\":2,   \"a\" : 1.50}\n";
+        assert_eq!(repair(valid), valid);
+    }
+
+    #[test]
+    fn test_edit_list() {
+        let input = "Sure!\n{'a': 1,\n \"é\": [1, 2}";
+        let (value, edits) = parse_with_edits(input, ParseOptions::default()).unwrap();
+        assert_eq!(
+            edits
+                .iter()
+                .map(|e| (&input[e.range.clone()], e.new_text.as_str(), e.kind))
+                .collect::<Vec<_>>(),
+            vec![
+                ("Sure!\n", "", EditKind::RemovedText),
+                ("'", "\"", EditKind::ReplacedQuote),
+                ("'", "\"", EditKind::ReplacedQuote),
+                ("}", "]", EditKind::ReplacedCloser),
+                ("", "}", EditKind::InsertedCloser),
+            ]
+        );
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&apply_edits(input, &edits)).unwrap(),
+            jsonish_to_serde(&value)
+        );
+        assert_eq!(edits[3].lsp_range(input), ((2, 11), (2, 12)));
+    }
+
+    #[test]
+    fn test_replace_json() {
+        let replace = |original: &str, new: serde_json::Value| {
+            let options = ParseOptions::default().interpret_yaml(true);
+            let parsed = parse(original, options).unwrap();
+            let new = serde_json::from_value::<Value>(new).unwrap();
+            replace_json(original, &parsed, &new).unwrap()
+        };
+        assert_eq!(
+            replace(
+                "Here you go:\n```json\n{\"name\": \"Alice\", ssn: 123-45,}\n```\nAnything else?",
+                serde_json::json!({"name": "Alice", "ssn": "[redacted]"})
+            ),
+            "Here you go:\n```json\n{\"name\":\"Alice\",\"ssn\":\"[redacted]\"}\n```\nAnything else?"
+        );
+        assert_eq!(
+            replace(
+                "<answer>\n  {\n    \"a\": 1\n  }\n</answer> Done.",
+                serde_json::json!({"a": 2})
+            ),
+            "<answer>\n  {\n    \"a\": 2\n  }\n</answer> Done."
+        );
+        // The answer, not the same value in the reasoning before it
+        assert_eq!(
+            replace(
+                "<think>Like {\"a\": 1}?</think>\n{\"a\": 1}",
+                serde_json::json!({"a": 2})
+            ),
+            "<think>Like {\"a\": 1}?</think>\n{\"a\":2}"
+        );
+        // Links before the JSON do not use up the regions tried
+        let links =
+            "See [the docs](https://example.com/docs) and [more](https://example.com). ".repeat(40);
+        let original = format!("{links}\n{{\"a\": 1");
+        let parsed = parse("{\"a\": 1", ParseOptions::default()).unwrap();
+        let new = serde_json::from_value::<Value>(serde_json::json!({"a": 2})).unwrap();
+        assert_eq!(
+            replace_json(&original, &parsed, &new).unwrap(),
+            format!("{links}\n{{\"a\":2}}")
+        );
+        assert_eq!(
+            replace(
+                "Example: ```json\n[1]\n```\nAnswer:\n```json\n[1]\n```",
+                serde_json::json!([2])
+            ),
+            "Example: ```json\n[1]\n```\nAnswer:\n```json\n[2]\n```"
+        );
+        assert_eq!(
+            replace("The total is [1, 2", serde_json::json!([3])),
+            "The total is [3]"
+        );
+        assert_eq!(
+            replace(
+                "```yaml\nname: Bob\nage: 4\n```",
+                serde_json::json!({"name": "Bob", "age": 42})
+            ),
+            "```yaml\n{\n  \"age\": 42,\n  \"name\": \"Bob\"\n}\n```"
+        );
+    }
+
+    #[test]
+    fn test_falls_back_to_serializing() {
+        let options = ParseOptions::default().interpret_yaml(true);
+        assert_eq!(
+            repair_source("name: Alice\nage: 30", options).unwrap(),
+            "{\"age\":30,\"name\":\"Alice\"}"
+        );
+    }
+}
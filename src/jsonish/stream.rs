@@ -0,0 +1,503 @@
+use anyhow::Result;
+
+use crate::jsonish::{
+    parse,
+    provider::{add_event, SseEvents},
+    ParseOptions, ProviderResponse, Value,
+};
+
+/// Decodes a server-sent event stream of a chat completion as it arrives and
+/// parses the text streamed so far.
+///
+/// Feed the raw bytes with [`push`](Self::push) in whatever chunks the
+/// connection delivers: lines and UTF-8 characters split across chunks are
+/// buffered. The deltas of OpenAI-compatible (`choices[].delta.content`,
+/// `response.output_text.delta`), Anthropic (`content_block_delta`) and
+/// Gemini events are accumulated as in [`read_response`](super::read_response).
+///
+/// While the text is a single JSON document (after any preamble such as
+/// ```` ```json ````), snapshots come from an incremental parser that only
+/// reads the new text, so an update costs the size of the delta plus a copy
+/// of the value rather than a parse of the whole transcript. Before a
+/// document starts, and once the text stops looking like strict JSON, each
+/// snapshot re-runs [`parse`] on all of it. [`finish`](Self::finish) always
+/// returns the result of [`parse`].
+#[derive(Debug)]
+pub struct StreamDecoder {
+    options: ParseOptions,
+    /// Bytes of a line that has not ended yet.
+    line: Vec<u8>,
+    sse: SseEvents,
+    response: Option<ProviderResponse>,
+    json: IncrementalJson,
+    /// How much of the response text `json` has read.
+    read: usize,
+}
+
+impl StreamDecoder {
+    pub fn new(options: ParseOptions) -> Self {
+        Self {
+            options,
+            line: vec![],
+            sse: SseEvents::default(),
+            response: None,
+            json: IncrementalJson::default(),
+            read: 0,
+        }
+    }
+
+    /// Takes the next bytes of the stream. Returns a snapshot of the value
+    /// parsed from the text so far when they added to the text.
+    pub fn push(&mut self, bytes: &[u8]) -> Option<Value> {
+        let mut rest = bytes;
+        while let Some(idx) = rest.iter().position(|&b| b == b'\n') {
+            self.line.extend_from_slice(&rest[..idx]);
+            rest = &rest[idx + 1..];
+            let line = std::mem::take(&mut self.line);
+            self.read_line(&String::from_utf8_lossy(&line));
+        }
+        self.line.extend_from_slice(rest);
+        self.update()
+    }
+
+    /// Ends the stream, reading an event left without its closing blank
+    /// line, and parses the whole text.
+    pub fn finish(&mut self) -> Result<Value> {
+        let line = std::mem::take(&mut self.line);
+        self.read_line(&String::from_utf8_lossy(&line));
+        self.read_line("");
+        let response = self
+            .response
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Not a known provider response"))?;
+        parse(&response.text, self.options)
+    }
+
+    /// The text and metadata received so far.
+    pub fn response(&self) -> Option<&ProviderResponse> {
+        self.response.as_ref()
+    }
+
+    fn read_line(&mut self, line: &str) {
+        if let Some(event) = self.sse.line(line) {
+            add_event(&mut self.response, &event);
+        }
+    }
+
+    fn update(&mut self) -> Option<Value> {
+        let text = &self.response.as_ref()?.text;
+        if text.len() == self.read {
+            return None;
+        }
+        for c in text[self.read..].chars() {
+            self.json.push(c);
+        }
+        self.read = text.len();
+
+        if self.json.failed || self.json.expect == Expect::Start {
+            parse(text, self.options).ok()
+        } else {
+            self.json.snapshot()
+        }
+    }
+}
+
+/// A strict JSON parser that reads one character at a time and can produce
+/// the value read so far at any point. Text before the first `{` or `[` and
+/// after the document closes is ignored.
+#[derive(Debug, Default)]
+struct IncrementalJson {
+    /// The collections that are open, outermost first.
+    stack: Vec<Frame>,
+    token: Token,
+    expect: Expect,
+    root: Option<Value>,
+    /// The text is not strict JSON.
+    failed: bool,
+}
+
+#[derive(Debug)]
+enum Frame {
+    Object {
+        fields: Vec<(String, Value)>,
+        key: Option<String>,
+    },
+    Array(Vec<Value>),
+}
+
+#[derive(Debug, Default)]
+enum Token {
+    #[default]
+    None,
+    String {
+        text: String,
+        key: bool,
+        /// The escape sequence being read, without its `\`.
+        escape: Option<String>,
+        /// A high surrogate waiting for the low one.
+        surrogate: Option<u32>,
+    },
+    Number(String),
+    Literal(String),
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+enum Expect {
+    /// Skipping text before the document.
+    #[default]
+    Start,
+    Value,
+    /// After `[`.
+    ValueOrEnd,
+    /// After `{`.
+    KeyOrEnd,
+    Key,
+    Colon,
+    CommaOrEnd,
+    Done,
+}
+
+impl IncrementalJson {
+    fn push(&mut self, c: char) {
+        if self.failed || self.expect == Expect::Done {
+            return;
+        }
+        if !self.push_token(c) {
+            self.failed = !self.push_structure(c);
+        }
+    }
+
+    /// Feeds `c` to the token being read. Returns false when `c` is not part
+    /// of it.
+    fn push_token(&mut self, c: char) -> bool {
+        match &mut self.token {
+            Token::None => false,
+            Token::String {
+                text,
+                key,
+                escape,
+                surrogate,
+            } => {
+                match escape {
+                    Some(seq) => {
+                        seq.push(c);
+                        match unescape(seq, surrogate) {
+                            Ok(Some(c)) => {
+                                text.push(c);
+                                *escape = None;
+                            }
+                            Ok(None) if seq.len() < 5 => {}
+                            Ok(None) => *escape = None,
+                            Err(()) => self.failed = true,
+                        }
+                    }
+                    None if c == '\\' => *escape = Some(String::new()),
+                    // A lone high surrogate: `parse` keeps its escape as text
+                    None if surrogate.is_some() => self.failed = true,
+                    None if c == '"' => {
+                        let (text, key) = (std::mem::take(text), *key);
+                        self.token = Token::None;
+                        if key {
+                            if let Some(Frame::Object { key, .. }) = self.stack.last_mut() {
+                                *key = Some(text);
+                            }
+                            self.expect = Expect::Colon;
+                        } else {
+                            self.add(Value::String(text));
+                        }
+                    }
+                    None => text.push(c),
+                }
+                true
+            }
+            Token::Number(digits) => {
+                if c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E') {
+                    digits.push(c);
+                    return true;
+                }
+                match digits.parse::<serde_json::Number>() {
+                    Ok(n) => {
+                        self.token = Token::None;
+                        self.add(Value::Number(n));
+                    }
+                    Err(_) => self.failed = true,
+                }
+                false
+            }
+            Token::Literal(word) => {
+                word.push(c);
+                let value = match word.as_str() {
+                    "true" => Value::Boolean(true),
+                    "false" => Value::Boolean(false),
+                    "null" => Value::Null,
+                    w if ["true", "false", "null"].iter().any(|l| l.starts_with(w)) => {
+                        return true;
+                    }
+                    _ => {
+                        self.failed = true;
+                        return true;
+                    }
+                };
+                self.token = Token::None;
+                self.add(value);
+                true
+            }
+        }
+    }
+
+    /// Reads `c` between tokens. Returns false when it is not valid JSON.
+    fn push_structure(&mut self, c: char) -> bool {
+        if self.failed {
+            return false;
+        }
+        match self.expect {
+            Expect::Start => {
+                if c == '{' || c == '[' {
+                    self.open(c);
+                }
+                return true;
+            }
+            Expect::Done => return true,
+            _ if c.is_whitespace() => return true,
+            _ => {}
+        }
+        match (&self.expect, c) {
+            (Expect::Value | Expect::ValueOrEnd, '{' | '[') => self.open(c),
+            (Expect::Value | Expect::ValueOrEnd, '"') => self.start_string(false),
+            (Expect::Value | Expect::ValueOrEnd, '-' | '0'..='9') => {
+                self.token = Token::Number(c.to_string())
+            }
+            (Expect::Value | Expect::ValueOrEnd, 't' | 'f' | 'n') => {
+                self.token = Token::Literal(c.to_string())
+            }
+            (Expect::ValueOrEnd, ']') | (Expect::KeyOrEnd, '}') => self.close(),
+            (Expect::KeyOrEnd | Expect::Key, '"') => self.start_string(true),
+            (Expect::Colon, ':') => self.expect = Expect::Value,
+            (Expect::CommaOrEnd, ',') => {
+                self.expect = match self.stack.last() {
+                    Some(Frame::Object { .. }) => Expect::Key,
+                    _ => Expect::Value,
+                }
+            }
+            (Expect::CommaOrEnd, '}')
+                if matches!(self.stack.last(), Some(Frame::Object { .. })) =>
+            {
+                self.close()
+            }
+            (Expect::CommaOrEnd, ']') if matches!(self.stack.last(), Some(Frame::Array(_))) => {
+                self.close()
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    fn start_string(&mut self, key: bool) {
+        self.token = Token::String {
+            text: String::new(),
+            key,
+            escape: None,
+            surrogate: None,
+        };
+    }
+
+    fn open(&mut self, c: char) {
+        if c == '{' {
+            self.stack.push(Frame::Object {
+                fields: vec![],
+                key: None,
+            });
+            self.expect = Expect::KeyOrEnd;
+        } else {
+            self.stack.push(Frame::Array(vec![]));
+            self.expect = Expect::ValueOrEnd;
+        }
+    }
+
+    fn close(&mut self) {
+        if let Some(frame) = self.stack.pop() {
+            self.add(frame.into_value());
+        }
+    }
+
+    /// Adds a complete value to the innermost collection.
+    fn add(&mut self, value: Value) {
+        match self.stack.last_mut() {
+            Some(Frame::Object { fields, key }) => {
+                fields.push((key.take().unwrap_or_default(), value));
+                self.expect = Expect::CommaOrEnd;
+            }
+            Some(Frame::Array(items)) => {
+                items.push(value);
+                self.expect = Expect::CommaOrEnd;
+            }
+            None => {
+                self.root = Some(value);
+                self.expect = Expect::Done;
+            }
+        }
+    }
+
+    /// The value read so far, with open strings and collections closed.
+    fn snapshot(&self) -> Option<Value> {
+        if let Some(root) = &self.root {
+            return Some(root.clone());
+        }
+        let mut value = match &self.token {
+            Token::String {
+                text, key: false, ..
+            } => Some(Value::String(text.clone())),
+            Token::Number(digits) => digits.parse().ok().map(Value::Number),
+            _ => None,
+        };
+        for frame in self.stack.iter().rev() {
+            value = Some(frame.to_value(value));
+        }
+        value
+    }
+}
+
+impl Frame {
+    /// The collection with `last` appended as its last value.
+    fn to_value(&self, last: Option<Value>) -> Value {
+        match self {
+            Frame::Object { fields, key } => {
+                let mut fields = fields.clone();
+                if let (Some(key), Some(last)) = (key, last) {
+                    fields.push((key.clone(), last));
+                }
+                Value::Object(fields)
+            }
+            Frame::Array(items) => {
+                let mut items = items.clone();
+                items.extend(last);
+                Value::Array(items)
+            }
+        }
+    }
+
+    fn into_value(self) -> Value {
+        match self {
+            Frame::Object { fields, .. } => Value::Object(fields),
+            Frame::Array(items) => Value::Array(items),
+        }
+    }
+}
+
+/// Decodes the escape sequence `seq` (without its `\`). `Ok(None)` means more
+/// characters are needed, or that a high surrogate was stored in `surrogate`.
+/// Lone surrogates are an error, leaving them to [`parse`].
+fn unescape(seq: &str, surrogate: &mut Option<u32>) -> std::result::Result<Option<char>, ()> {
+    let c = match seq {
+        "\"" => '"',
+        "\\" => '\\',
+        "/" => '/',
+        "b" => '\u{8}',
+        "f" => '\u{c}',
+        "n" => '\n',
+        "r" => '\r',
+        "t" => '\t',
+        s if s.starts_with('u') && s.len() < 5 => return Ok(None),
+        s if s.starts_with('u') => {
+            // `from_str_radix` would also accept a sign: `\u+041`
+            if !s[1..].bytes().all(|b| b.is_ascii_hexdigit()) {
+                return Err(());
+            }
+            let code = u32::from_str_radix(&s[1..], 16).map_err(|_| ())?;
+            match (surrogate.take(), code) {
+                (None, 0xD800..=0xDBFF) => {
+                    *surrogate = Some(code);
+                    return Ok(None);
+                }
+                (Some(high), 0xDC00..=0xDFFF) => {
+                    char::from_u32(0x10000 + ((high - 0xD800) << 10) + (code - 0xDC00)).ok_or(())?
+                }
+                (None, code) => char::from_u32(code).ok_or(())?,
+                (Some(_), _) => return Err(()),
+            }
+        }
+        _ => return Err(()),
+    };
+    if surrogate.is_some() {
+        return Err(());
+    }
+    Ok(Some(c))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jsonish::jsonish_to_serde;
+
+    fn snapshots(json: &str) -> Vec<Option<serde_json::Value>> {
+        let mut parser = IncrementalJson::default();
+        json.chars()
+            .map(|c| {
+                parser.push(c);
+                parser.snapshot().map(|v| jsonish_to_serde(&v))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_incremental_snapshots() {
+        let found = snapshots("```json\n{\"a\": [1, \"x\\u00e9\\ud83d\\ude00\"], \"b\": tru");
+        assert_eq!(found[7], None);
+        assert_eq!(found[8], Some(serde_json::json!({})));
+        assert_eq!(found[14], Some(serde_json::json!({"a": []})));
+        assert_eq!(found[15], Some(serde_json::json!({"a": [1]})));
+        assert_eq!(found[19], Some(serde_json::json!({"a": [1, "x"]})));
+        assert_eq!(
+            found.last().unwrap(),
+            &Some(serde_json::json!({"a": [1, "xé😀"]}))
+        );
+
+        let found = snapshots("[{\"k\": null}, -2.5e3]\n```");
+        assert_eq!(
+            found.last().unwrap(),
+            &Some(serde_json::json!([{"k": null}, -2500.0]))
+        );
+    }
+
+    #[test]
+    fn test_not_strict_json_fails() {
+        for input in [
+            "{a: 1}",
+            "[1,]x",
+            "{\"a\" 1}",
+            "[1, 2}",
+            "[nul1]",
+            "[\"\\u+041\"]",
+            "[\"\\ud83d x\"]",
+            "[\"\\ud83d\\n\"]",
+            "[\"\\ud83d\\u0041\"]",
+            "[\"\\ude00\"]",
+        ] {
+            let mut parser = IncrementalJson::default();
+            input.chars().for_each(|c| parser.push(c));
+            assert!(parser.failed, "{input}");
+        }
+    }
+
+    #[test]
+    fn test_decoder_falls_back_to_parse() {
+        let stream =
+            "data: {\"choices\":[{\"index\":0,\"delta\":{\"content\":\"{name: \\\"Al\"}}]}\n\n";
+        let mut decoder = StreamDecoder::new(ParseOptions::default());
+        let value = decoder.push(stream.as_bytes()).unwrap();
+        assert_eq!(jsonish_to_serde(&value), serde_json::json!({"name": "Al"}));
+
+        // A lone surrogate reads as `parse` reads it
+        let text = "{\"a\": \"\\ud83d x\\u0041\"}";
+        let stream = format!(
+            "data: {}\n\n",
+            serde_json::json!({"choices": [{"index": 0, "delta": {"content": text}}]})
+        );
+        let mut decoder = StreamDecoder::new(ParseOptions::default());
+        let value = decoder.push(stream.as_bytes()).unwrap();
+        assert_eq!(
+            jsonish_to_serde(&value),
+            jsonish_to_serde(&parse(text, ParseOptions::default()).unwrap())
+        );
+    }
+}
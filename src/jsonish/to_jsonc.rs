@@ -0,0 +1,90 @@
+use crate::jsonish::{jsonish_to_serde, Value};
+
+/// Converts a `jsonish::Value` into a pretty printed JSONC string, writing the
+/// comments kept by the parser as `//` lines above the value they belong to.
+///
+/// Returns an error if serializing a scalar via `serde_json` fails.
+pub fn to_jsonc_string_pretty(value: &Value) -> Result<String, serde_json::Error> {
+    let mut out = String::new();
+    let (comments, value) = unwrap(value);
+    write_comments(&comments, 0, &mut out);
+    write_value(value, 0, &mut out)?;
+    Ok(out)
+}
+
+/// Strips the wrapper variants, collecting the comments found on the way.
+fn unwrap(mut value: &Value) -> (Vec<&str>, &Value) {
+    let mut comments = vec![];
+    loop {
+        value = match value {
+            Value::Commented(inner, c) => {
+                comments.extend(c.iter().map(String::as_str));
+                inner
+            }
+            Value::Markdown(_, inner) | Value::FixedJson(inner, _) => inner,
+            // Same choice as `jsonish_to_serde`
+            Value::AnyOf(items, _) => match items
+                .iter()
+                .find(|v| !matches!(jsonish_to_serde(v), serde_json::Value::Null))
+            {
+                Some(item) => item,
+                None => return (comments, &Value::Null),
+            },
+            _ => return (comments, value),
+        }
+    }
+}
+
+fn write_comments(comments: &[&str], indent: usize, out: &mut String) {
+    for line in comments.iter().flat_map(|c| c.lines()) {
+        push_indent(indent, out);
+        out.push_str("// ");
+        out.push_str(line.trim());
+        out.push('\n');
+    }
+}
+
+fn write_value(value: &Value, indent: usize, out: &mut String) -> Result<(), serde_json::Error> {
+    match value {
+        Value::Object(fields) if !fields.is_empty() => {
+            out.push_str("{\n");
+            for (i, (key, v)) in fields.iter().enumerate() {
+                let (comments, v) = unwrap(v);
+                write_comments(&comments, indent + 1, out);
+                push_indent(indent + 1, out);
+                out.push_str(&serde_json::to_string(key)?);
+                out.push_str(": ");
+                write_value(v, indent + 1, out)?;
+                if i + 1 < fields.len() {
+                    out.push(',');
+                }
+                out.push('\n');
+            }
+            push_indent(indent, out);
+            out.push('}');
+        }
+        Value::Array(items) if !items.is_empty() => {
+            out.push_str("[\n");
+            for (i, v) in items.iter().enumerate() {
+                let (comments, v) = unwrap(v);
+                write_comments(&comments, indent + 1, out);
+                push_indent(indent + 1, out);
+                write_value(v, indent + 1, out)?;
+                if i + 1 < items.len() {
+                    out.push(',');
+                }
+                out.push('\n');
+            }
+            push_indent(indent, out);
+            out.push(']');
+        }
+        v => out.push_str(&serde_json::to_string(&jsonish_to_serde(v))?),
+    }
+    Ok(())
+}
+
+fn push_indent(indent: usize, out: &mut String) {
+    for _ in 0..indent {
+        out.push_str("  ");
+    }
+}
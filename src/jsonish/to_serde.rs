@@ -17,6 +17,7 @@ pub fn jsonish_to_serde(value: &jsonish::Value) -> serde_json::Value {
         }
         jsonish::Value::Markdown(_, inner) => jsonish_to_serde(inner),
         jsonish::Value::FixedJson(inner, _) => jsonish_to_serde(inner),
+        jsonish::Value::Commented(inner, _) => jsonish_to_serde(inner),
         jsonish::Value::AnyOf(values, _) => values
             .iter()
             .find_map(|v| match jsonish_to_serde(v) {
@@ -0,0 +1,372 @@
+use std::{ops::Range, sync::OnceLock};
+
+use regex::Regex;
+
+use crate::jsonish::{jsonish_to_serde, parse, ParseOptions, Value};
+
+/// The envelope a [`ToolCall`] was found in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ToolCallFormat {
+    /// `{"name": ..., "arguments": "<json string>"}`, bare or inside
+    /// `{"type": "function", "function": {...}}` / `tool_calls` / `function_call`.
+    OpenAi,
+    /// `{"type": "tool_use", "id": ..., "name": ..., "input": {...}}`.
+    Anthropic,
+    /// `Action: name` followed by `Action Input: ...` lines.
+    React,
+    /// `<tool_call>{"name": ..., "arguments": {...}}</tool_call>`.
+    Tagged,
+}
+
+/// A tool call found by [`extract_tool_calls`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ToolCall {
+    pub name: String,
+    /// The call id, when the envelope carries one.
+    pub id: Option<String>,
+    /// The repaired arguments. Arguments that are not JSON are kept as a
+    /// `Value::String`.
+    pub arguments: Value,
+    pub format: ToolCallFormat,
+    /// Byte range of the envelope in the input: the tags, the `Action` lines
+    /// or the JSON document the call was found in.
+    pub span: Range<usize>,
+}
+
+/// Finds the tool calls in raw model output, in the order they appear.
+///
+/// Each arguments payload, including `arguments` fields that hold JSON
+/// encoded as a string, is repaired with [`parse`] using `options`.
+pub fn extract_tool_calls(str: &str, options: ParseOptions) -> Vec<ToolCall> {
+    let mut calls = vec![];
+    let mut taken: Vec<Range<usize>> = vec![];
+
+    for (span, content) in tagged(str) {
+        let start = calls.len();
+        if let Ok(value) = parse(content, options) {
+            find_calls(&value, options, &span, &mut calls);
+            // Inside the tags a name is enough
+            match resolved(&value) {
+                Value::Object(fields) if calls.len() == start => {
+                    if let Some(name) = string_field(fields, "name") {
+                        calls.push(ToolCall {
+                            name,
+                            id: string_field(fields, "id"),
+                            arguments: Value::Object(vec![]),
+                            format: ToolCallFormat::Tagged,
+                            span: span.clone(),
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+        for call in &mut calls[start..] {
+            call.format = ToolCallFormat::Tagged;
+        }
+        taken.push(span);
+    }
+
+    for (span, name, input) in react(str) {
+        if taken.iter().any(|t| t.contains(&span.start)) {
+            continue;
+        }
+        calls.push(ToolCall {
+            name: name.to_string(),
+            id: None,
+            arguments: arguments(&Value::String(input.to_string()), options),
+            format: ToolCallFormat::React,
+            span: span.clone(),
+        });
+        taken.push(span);
+    }
+
+    let mut offset = 0;
+    while let Some(idx) = str[offset..].find('{') {
+        let start = offset + idx;
+        if let Some(t) = taken.iter().find(|t| t.contains(&start)) {
+            offset = t.end;
+            continue;
+        }
+        let span = start..start + object_len(&str[start..]);
+        if let Ok(value) = parse(&str[span.clone()], options) {
+            find_calls(&value, options, &span, &mut calls);
+        }
+        offset = span.end;
+    }
+
+    calls.sort_by_key(|call| call.span.start);
+    calls
+}
+
+/// `<tool_call>...</tool_call>` blocks and their content. An unclosed block
+/// runs to the end of the input.
+fn tagged(str: &str) -> Vec<(Range<usize>, &str)> {
+    const OPEN: &str = "<tool_call>";
+    const CLOSE: &str = "</tool_call>";
+
+    let mut blocks = vec![];
+    let mut offset = 0;
+    while let Some(idx) = str[offset..].find(OPEN) {
+        let start = offset + idx;
+        let content_start = start + OPEN.len();
+        let (content_end, end) = match str[content_start..].find(CLOSE) {
+            Some(idx) => (content_start + idx, content_start + idx + CLOSE.len()),
+            None => (str.len(), str.len()),
+        };
+        blocks.push((start..end, str[content_start..content_end].trim()));
+        offset = end;
+    }
+    blocks
+}
+
+const ACTION: &str = r"(?m)^[ \t]*Action:[ \t]*`?([\w.\-]+)`?[ \t]*\r?\n[ \t]*Action Input:[ \t]*";
+const NEXT_STEP: &str = r"(?m)^[ \t]*(Observation:|Thought:|Action:|Final Answer:|<tool_call>)";
+
+/// `Action:` / `Action Input:` pairs. The input runs until the next ReAct
+/// line (`Observation:`, `Thought:`, ...), a `<tool_call>` tag or the end of
+/// the input.
+fn react(str: &str) -> Vec<(Range<usize>, &str, &str)> {
+    static ACTION_REGEX: OnceLock<Regex> = OnceLock::new();
+    static NEXT_STEP_REGEX: OnceLock<Regex> = OnceLock::new();
+
+    // Constant patterns, compiled by `test_react_patterns_compile`
+    let action = ACTION_REGEX.get_or_init(|| Regex::new(ACTION).expect("valid ACTION pattern"));
+    let next_step =
+        NEXT_STEP_REGEX.get_or_init(|| Regex::new(NEXT_STEP).expect("valid NEXT_STEP pattern"));
+
+    action
+        .captures_iter(str)
+        .filter_map(|cap| {
+            let whole = cap.get(0)?;
+            let name = cap.get(1)?.as_str();
+            let end = next_step
+                .find(&str[whole.end()..])
+                .map_or(str.len(), |m| whole.end() + m.start());
+            let input = &str[whole.end()..end];
+            let span_end = whole.end() + input.trim_end().len();
+            Some((whole.start()..span_end, name, input.trim()))
+        })
+        .collect()
+}
+
+/// The length of the object at the start of `str`, or of the rest of `str`
+/// if it is never closed.
+pub(crate) fn object_len(str: &str) -> usize {
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (idx, c) in str.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            _ if in_string => {}
+            '{' | '[' => depth += 1,
+            '}' | ']' => {
+                depth = depth.saturating_sub(1);
+                if depth == 0 {
+                    return idx + 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    str.len()
+}
+
+/// The candidate of `value` that `jsonish_to_serde` would pick, without its
+/// wrappers.
+fn resolved(value: &Value) -> &Value {
+    match value {
+        Value::Markdown(_, inner) | Value::FixedJson(inner, _) | Value::Commented(inner, _) => {
+            resolved(inner)
+        }
+        Value::AnyOf(items, _) => items
+            .iter()
+            .find(|v| !matches!(jsonish_to_serde(v), serde_json::Value::Null))
+            .map_or(value, resolved),
+        v => v,
+    }
+}
+
+fn field<'a>(fields: &'a [(String, Value)], key: &str) -> Option<&'a Value> {
+    fields
+        .iter()
+        .find(|(k, _)| k == key)
+        .map(|(_, v)| resolved(v))
+}
+
+fn string_field(fields: &[(String, Value)], key: &str) -> Option<String> {
+    match field(fields, key) {
+        Some(Value::String(s)) => Some(s.clone()),
+        _ => None,
+    }
+}
+
+/// Repairs an arguments payload, decoding arguments sent as a JSON string.
+fn arguments(value: &Value, options: ParseOptions) -> Value {
+    match resolved(value) {
+        Value::String(s) => match parse(s, options) {
+            Ok(parsed) if !matches!(resolved(&parsed), Value::String(_)) => parsed,
+            _ => Value::String(s.trim().to_string()),
+        },
+        v => v.clone(),
+    }
+}
+
+fn find_calls(value: &Value, options: ParseOptions, span: &Range<usize>, out: &mut Vec<ToolCall>) {
+    match resolved(value) {
+        Value::Object(fields) => {
+            let id = string_field(fields, "id");
+            let call = |name, arguments: Option<&Value>, format| ToolCall {
+                name,
+                id: id.clone(),
+                arguments: arguments.map_or(Value::Object(vec![]), |a| self::arguments(a, options)),
+                format,
+                span: span.clone(),
+            };
+
+            if let Some(name) = string_field(fields, "name") {
+                if string_field(fields, "type").as_deref() == Some("tool_use") {
+                    out.push(call(
+                        name,
+                        field(fields, "input"),
+                        ToolCallFormat::Anthropic,
+                    ));
+                    return;
+                }
+                if let Some(args) = field(fields, "arguments").or(field(fields, "parameters")) {
+                    out.push(call(name, Some(args), ToolCallFormat::OpenAi));
+                    return;
+                }
+            }
+            if let Some(Value::Object(function)) = field(fields, "function") {
+                if let Some(name) = string_field(function, "name") {
+                    out.push(call(
+                        name,
+                        field(function, "arguments"),
+                        ToolCallFormat::OpenAi,
+                    ));
+                    return;
+                }
+            }
+
+            for (_, v) in fields {
+                find_calls(v, options, span, out);
+            }
+        }
+        Value::Array(items) => {
+            for v in items {
+                find_calls(v, options, span, out);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn calls(
+        str: &str,
+    ) -> Vec<(
+        String,
+        Option<String>,
+        serde_json::Value,
+        ToolCallFormat,
+        &str,
+    )> {
+        extract_tool_calls(str, ParseOptions::default())
+            .into_iter()
+            .map(|c| {
+                (
+                    c.name,
+                    c.id,
+                    jsonish_to_serde(&c.arguments),
+                    c.format,
+                    &str[c.span],
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_openai_and_anthropic_envelopes() {
+        let input = r#"{"tool_calls": [{"id": "call_1", "type": "function", "function": {"name": "search", "arguments": "{\"query\": \"rust json\", \"limit\": 5"}}]}
+then [{"type": "tool_use", "id": "toolu_1", "name": "get_weather", "input": {"city": "Paris",}}]"#;
+        let found = calls(input);
+        assert_eq!(found.len(), 2);
+        assert_eq!(
+            (&found[0].0, &found[0].1, &found[0].2, found[0].3),
+            (
+                &"search".to_string(),
+                &Some("call_1".to_string()),
+                &serde_json::json!({"query": "rust json", "limit": 5}),
+                ToolCallFormat::OpenAi
+            )
+        );
+        assert!(found[0].4.starts_with("{\"tool_calls\"") && found[0].4.ends_with("}]}"));
+        assert_eq!(
+            (&found[1].0, &found[1].1, &found[1].2, found[1].3),
+            (
+                &"get_weather".to_string(),
+                &Some("toolu_1".to_string()),
+                &serde_json::json!({"city": "Paris"}),
+                ToolCallFormat::Anthropic
+            )
+        );
+    }
+
+    #[test]
+    fn test_react_and_tagged_calls() {
+        let input = "Thought: I should look this up.\nAction: search\nAction Input: {\"query\": \"rust\"\nObservation: ...\nAction: `lookup`\nAction Input: the borrow checker\n\n<tool_call>\n{\"name\": \"get_time\", \"arguments\": {tz: 'UTC'}}\n</tool_call>\n<tool_call>{\"name\": \"noop\"";
+        assert_eq!(
+            calls(input),
+            vec![
+                (
+                    "search".to_string(),
+                    None,
+                    serde_json::json!({"query": "rust"}),
+                    ToolCallFormat::React,
+                    "Action: search\nAction Input: {\"query\": \"rust\"",
+                ),
+                (
+                    "lookup".to_string(),
+                    None,
+                    serde_json::json!("the borrow checker"),
+                    ToolCallFormat::React,
+                    "Action: `lookup`\nAction Input: the borrow checker",
+                ),
+                (
+                    "get_time".to_string(),
+                    None,
+                    serde_json::json!({"tz": "UTC"}),
+                    ToolCallFormat::Tagged,
+                    "<tool_call>\n{\"name\": \"get_time\", \"arguments\": {tz: 'UTC'}}\n</tool_call>",
+                ),
+                (
+                    "noop".to_string(),
+                    None,
+                    serde_json::json!({}),
+                    ToolCallFormat::Tagged,
+                    "<tool_call>{\"name\": \"noop\"",
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_react_patterns_compile() {
+        for pattern in [ACTION, NEXT_STEP] {
+            assert!(Regex::new(pattern).is_ok(), "{pattern}");
+        }
+    }
+
+    #[test]
+    fn test_plain_json_is_not_a_call() {
+        assert!(calls(r#"{"name": "Alice", "age": 30}"#).is_empty());
+        assert!(calls("No tools needed.").is_empty());
+    }
+}
@@ -1,3 +1,4 @@
+use crate::jsonish::NormalizationKind;
 use std::{
     collections::HashSet,
     hash::{Hash, Hasher},
@@ -7,6 +8,50 @@ use std::{
 pub enum Fixes {
     GreppedForJSON,
     InferredArray,
+    RenamedKey {
+        from: String,
+        to: String,
+    },
+    DroppedKey(String),
+    CollectedKey {
+        key: String,
+        into: String,
+    },
+    DecodedJsonString,
+    NormalizedInput(NormalizationKind),
+    InsertedColon,
+    ReplacedSeparator(String),
+    AlternativeInterpretation,
+    /// A closer of the wrong kind was read as the one expected: `[1, 2}`.
+    ReplacedCloser {
+        from: char,
+        to: char,
+    },
+    /// A collection was closed because a closer for its parent was found.
+    InsertedCloser(char),
+    /// A closer that matched no open collection was ignored.
+    DroppedCloser(char),
+    /// A value was left out (`"a": ,`, `[1,,3]`); holds the key of an object
+    /// member.
+    MissingValue(Option<String>),
+    /// A value was written as `...` or `…`; holds the key of an object member.
+    ElidedValue(Option<String>),
+    /// The input was YAML (`key: value` lines, `- ` items) rather than JSON.
+    InterpretedYaml,
+    /// A markdown pipe table or a CSV / TSV block was read into an array of
+    /// row objects.
+    ConvertedTable,
+    /// Lists and `Key: value` lines of a prose answer were read as arrays and
+    /// objects.
+    StructuredProse,
+    /// A call written as code (`search(query="x")`) was read as
+    /// `{"name": ..., "arguments": {...}}`.
+    InterpretedCall,
+    /// A reasoning section (`<think>...</think>`, `Reasoning: ...`) was
+    /// removed before parsing; holds its text.
+    StrippedReasoning(String),
+    /// The value was found inside a reasoning section rather than the answer.
+    FoundInReasoning,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -25,6 +70,11 @@ pub enum Value {
     Markdown(String, Box<Value>),
     FixedJson(Box<Value>, Vec<Fixes>),
     AnyOf(Vec<Value>, String),
+
+    /// Comments found next to the value. Only produced with
+    /// `ParseOptions::keep_comments(true)`, but exhaustive matches on `Value`
+    /// need an arm for it.
+    Commented(Box<Value>, Vec<String>),
 }
 
 impl Hash for Value {
@@ -57,6 +107,7 @@ impl Hash for Value {
                     item.hash(state);
                 }
             }
+            Value::Commented(v, _) => v.hash(state),
         }
     }
 }
@@ -104,6 +155,71 @@ impl Value {
                 s.push(']');
                 s
             }
+            Value::Commented(inner, comments) => {
+                format!("{} ({} comments)", inner.r#type(), comments.len())
+            }
+        }
+    }
+
+    /// Attaches `comments` to the value, merging with any it already has.
+    pub(crate) fn with_comments(self, mut comments: Vec<String>) -> Value {
+        if comments.is_empty() {
+            return self;
+        }
+        match self {
+            Value::Commented(inner, mut existing) => {
+                existing.append(&mut comments);
+                Value::Commented(inner, existing)
+            }
+            v => Value::Commented(Box::new(v), comments),
+        }
+    }
+
+    /// All comments kept by the parser, keyed by the JSON pointer of the
+    /// value they were attached to.
+    ///
+    /// For `AnyOf` only the candidate picked by `jsonish_to_serde` is visited.
+    pub fn comments(&self) -> Vec<(String, String)> {
+        let mut comments = vec![];
+        collect_comments(self, &mut String::new(), &mut comments);
+        comments
+    }
+}
+
+fn collect_comments(value: &Value, path: &mut String, out: &mut Vec<(String, String)>) {
+    match value {
+        Value::String(_) | Value::Number(_) | Value::Boolean(_) | Value::Null => {}
+        Value::Object(fields) => {
+            for (k, v) in fields {
+                let len = path.len();
+                path.push('/');
+                path.push_str(&k.replace('~', "~0").replace('/', "~1"));
+                collect_comments(v, path, out);
+                path.truncate(len);
+            }
+        }
+        Value::Array(items) => {
+            for (i, v) in items.iter().enumerate() {
+                let len = path.len();
+                path.push_str(&format!("/{i}"));
+                collect_comments(v, path, out);
+                path.truncate(len);
+            }
+        }
+        Value::Markdown(_, inner) | Value::FixedJson(inner, _) => {
+            collect_comments(inner, path, out)
+        }
+        Value::AnyOf(items, _) => {
+            if let Some(item) = items
+                .iter()
+                .find(|v| !matches!(crate::jsonish::jsonish_to_serde(v), serde_json::Value::Null))
+            {
+                collect_comments(item, path, out);
+            }
+        }
+        Value::Commented(inner, comments) => {
+            out.extend(comments.iter().map(|c| (path.clone(), c.clone())));
+            collect_comments(inner, path, out);
         }
     }
 }
@@ -144,6 +260,7 @@ impl std::fmt::Display for Value {
                 }
                 write!(f, "]")
             }
+            Value::Commented(v, _) => write!(f, "{}", v),
         }
     }
 }
@@ -32,11 +32,278 @@ mod tests {
 
     // taken from commit https://github.com/boundaryml/baml/commit/8f758ef29cee811c124c234304d65bca281ee8d6
     #[test]
-    fn test_unquotes_strings(){
+    fn test_unquotes_strings() {
         let input = r#"  { rec_one: "and then i said \"hi\", and also \"bye\"", rec_two: "and then i said "hi", and also "bye"", "also_rec_one": ok }"#;
         // let output = jsonish::parse(input, ParseOptions::default()).unwrap();
         // let printable = jsonish::jsonish_to_serde(&output);
         // println!("{printable:?}");
         assert!(jsonish::parse(input, ParseOptions::default()).is_ok());
     }
+
+    #[test]
+    fn test_normalized_input() {
+        let input = "\u{feff}｛“name”：“Bob”，\u{a0}“note”：“he said “hi” ok”｝";
+        let value = jsonish::parse(
+            input,
+            ParseOptions::default().normalize_input(jsonish::NormalizeOptions::default()),
+        )
+        .unwrap();
+        assert_eq!(
+            jsonish::jsonish_to_serde(&value),
+            serde_json::json!({"name": "Bob", "note": "he said “hi” ok"})
+        );
+        let jsonish::Value::AnyOf(candidates, original) = &value else {
+            panic!("Expected AnyOf, got {:#?}", value);
+        };
+        assert_eq!(original, input);
+        let smart_quotes = jsonish::Fixes::NormalizedInput(jsonish::NormalizationKind::SmartQuote);
+        assert!(matches!(
+            &candidates[0],
+            jsonish::Value::FixedJson(_, fixes) if fixes.contains(&smart_quotes)
+        ));
+    }
+
+    #[test]
+    fn test_double_encoded_json() {
+        let input = r#""{\"a\": 1}""#;
+        let value = jsonish::parse(input, ParseOptions::default().decode_nested_json(2)).unwrap();
+        assert_eq!(
+            jsonish::jsonish_to_serde(&value),
+            serde_json::json!({"a": 1})
+        );
+
+        // Left alone unless enabled.
+        let value = jsonish::parse(input, ParseOptions::default()).unwrap();
+        assert_eq!(
+            jsonish::jsonish_to_serde(&value),
+            serde_json::json!("{\"a\": 1}")
+        );
+    }
+
+    #[test]
+    fn test_double_encoded_arguments() {
+        let input =
+            r#"{"name": "search", "arguments": "{\"query\": \"rust\", \"filters\": \"[1, 2]\"}"}"#;
+
+        let value = jsonish::parse(input, ParseOptions::default().decode_nested_json(1)).unwrap();
+        assert_eq!(
+            jsonish::jsonish_to_serde(&value),
+            serde_json::json!({"name": "search", "arguments": {"query": "rust", "filters": "[1, 2]"}})
+        );
+
+        let value = jsonish::parse(input, ParseOptions::default().decode_nested_json(2)).unwrap();
+        assert_eq!(
+            jsonish::jsonish_to_serde(&value),
+            serde_json::json!({"name": "search", "arguments": {"query": "rust", "filters": [1, 2]}})
+        );
+    }
+
+    #[test]
+    fn test_mismatched_closers() {
+        for (input, expected) in [
+            (
+                r#"{"a": [1, 2}, "b": 3}"#,
+                serde_json::json!({"a": [1, 2], "b": 3}),
+            ),
+            (r#"[{"a": 1]]"#, serde_json::json!([{"a": 1}])),
+            (r#"{"a": 1], "b": 2}"#, serde_json::json!({"a": 1, "b": 2})),
+            (r#"{"a": 1}}"#, serde_json::json!({"a": 1})),
+        ] {
+            let value = jsonish::parse(input, ParseOptions::default()).unwrap();
+            assert_eq!(jsonish::jsonish_to_serde(&value), expected, "{input}");
+        }
+    }
+
+    #[test]
+    fn test_yaml_answers() {
+        for (input, expected) in [
+            (
+                "name: Alice\nage: 30\ntags:\n  - a\n  - b",
+                serde_json::json!({"name": "Alice", "age": 30, "tags": ["a", "b"]}),
+            ),
+            (
+                "Here you go:\n```yaml\nname: Bob\n```",
+                serde_json::json!({"name": "Bob"}),
+            ),
+            ("Note: see below", serde_json::json!("Note: see below")),
+            // JSON in the text is preferred
+            (
+                "Reasoning: because x.\nAnswer: {\"a\": 1}",
+                serde_json::json!({"a": 1}),
+            ),
+        ] {
+            let options = ParseOptions::default().interpret_yaml(true);
+            let value = jsonish::parse(input, options).unwrap();
+            assert_eq!(jsonish::jsonish_to_serde(&value), expected, "{input}");
+        }
+
+        // Off by default: prose is not read as YAML
+        for input in ["Title: Foo\nAuthor: Bar", "- apples\n- pears"] {
+            let value = jsonish::parse(input, ParseOptions::default()).unwrap();
+            assert_eq!(jsonish::jsonish_to_serde(&value), serde_json::json!(input));
+        }
+    }
+
+    #[test]
+    fn test_markdown_tables() {
+        let input =
+            "Here are the users:\n\n| id | name |\n|----|------|\n| 1 | Ann |\n| 2 | Bo |\n";
+        let value = jsonish::parse(input, ParseOptions::default()).unwrap();
+        let jsonish::Value::AnyOf(candidates, _) = &value else {
+            panic!("Expected AnyOf, got {value:?}");
+        };
+        assert!(matches!(
+            &candidates[0],
+            jsonish::Value::FixedJson(_, fixes) if fixes == &[jsonish::Fixes::ConvertedTable]
+        ));
+        assert_eq!(
+            jsonish::jsonish_to_serde(&value),
+            serde_json::json!([{"id": 1, "name": "Ann"}, {"id": 2, "name": "Bo"}])
+        );
+
+        // JSON next to a table comes first, the table follows it
+        let candidates = |input: &str| {
+            let value = jsonish::parse(input, ParseOptions::default()).unwrap();
+            let jsonish::Value::AnyOf(candidates, _) = value else {
+                panic!("Expected AnyOf, got {value:?}");
+            };
+            candidates
+                .iter()
+                .map(jsonish::jsonish_to_serde)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            candidates(&format!("{input}\nTotal: {{\"x\": 1}}")),
+            vec![
+                serde_json::json!({"x": 1}),
+                serde_json::json!([{"id": 1, "name": "Ann"}, {"id": 2, "name": "Bo"}]),
+            ]
+        );
+        let input = "Here is the JSON: {\"name\": \"a\", \"n\": 1}\n\n| field | meaning |\n|---|---|\n| name | the name |";
+        assert_eq!(
+            candidates(input),
+            vec![
+                serde_json::json!({"name": "a", "n": 1}),
+                serde_json::json!([{"field": "name", "meaning": "the name"}]),
+            ]
+        );
+
+        // Code in an untagged block is not a table, and a table read from a
+        // block follows the JSON after it
+        let input = "Run:\n```\nfoo(a, b)\nbar(c, d)\n```\nthen {\"ok\": true}";
+        let value = jsonish::parse(input, ParseOptions::default()).unwrap();
+        assert_eq!(
+            jsonish::jsonish_to_serde(&value),
+            serde_json::json!({"ok": true})
+        );
+        let input = "Data:\n```text\nid,ok\n1,true\n```\nthen {\"ok\": true}";
+        assert_eq!(
+            candidates(input)[..2],
+            [
+                serde_json::json!({"ok": true}),
+                serde_json::json!([{"id": 1, "ok": true}]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_prose_structure() {
+        let input = "Sure!\n\n**Name:** Alice\n**Age:** 30";
+        let value = jsonish::parse(input, ParseOptions::default().interpret_prose(true)).unwrap();
+        let jsonish::Value::AnyOf(candidates, _) = &value else {
+            panic!("Expected AnyOf, got {value:?}");
+        };
+        assert_eq!(candidates[0], jsonish::Value::String(input.to_string()));
+        assert_eq!(
+            jsonish::jsonish_to_serde(&candidates[1]),
+            serde_json::json!({"Name": "Alice", "Age": 30})
+        );
+
+        let value = jsonish::parse(input, ParseOptions::default()).unwrap();
+        assert_eq!(value, jsonish::Value::String(input.to_string()));
+
+        // Prose is not repaired into a string document
+        for input in ["hello", "print(\"hi\")\n"] {
+            let value = jsonish::parse(input, ParseOptions::default()).unwrap();
+            assert_eq!(value, jsonish::Value::String(input.to_string()));
+            let value =
+                jsonish::parse(input, ParseOptions::default().interpret_prose(true)).unwrap();
+            assert_eq!(value, jsonish::Value::String(input.to_string()));
+        }
+        let value = jsonish::parse("[hello", ParseOptions::default()).unwrap();
+        assert_eq!(
+            jsonish::jsonish_to_serde(&value),
+            serde_json::json!(["hello"])
+        );
+    }
+
+    #[test]
+    fn test_function_calls() {
+        let input = "Thought: look it up\nAction: search(query=\"rust json\", limit=5)";
+        let value = jsonish::parse(input, ParseOptions::default().interpret_calls(true)).unwrap();
+        assert_eq!(
+            jsonish::jsonish_to_serde(&value),
+            serde_json::json!({"name": "search", "arguments": {"query": "rust json", "limit": 5}})
+        );
+
+        let value = jsonish::parse("search(query=\"rust\")", ParseOptions::default()).unwrap();
+        assert_eq!(
+            value,
+            jsonish::Value::String("search(query=\"rust\")".to_string())
+        );
+    }
+
+    #[test]
+    fn test_strip_reasoning() {
+        let input = "<think>Maybe {\"answer\": 1}? No.</think>\nThe result is {\"answer\": 2}";
+        let options = ParseOptions::default().strip_reasoning(jsonish::ReasoningOptions::default());
+        let value = jsonish::parse(input, options).unwrap();
+        assert_eq!(
+            jsonish::jsonish_to_serde(&value),
+            serde_json::json!({"answer": 2})
+        );
+        let jsonish::Value::AnyOf(candidates, _) = &value else {
+            panic!("Expected AnyOf, got {value:?}");
+        };
+        assert!(matches!(
+            &candidates[0],
+            jsonish::Value::FixedJson(_, fixes)
+                if fixes.contains(&jsonish::Fixes::StrippedReasoning("Maybe {\"answer\": 1}? No.".to_string()))
+        ));
+
+        // Tags and labels that are not reasoning sections
+        for (input, expected) in [
+            (
+                "{\"text\": \"use <think> tags\", \"b\": 1}",
+                serde_json::json!({"text": "use <think> tags", "b": 1}),
+            ),
+            (
+                "Reasoning: simple.\n{\"a\": 1}",
+                serde_json::json!({"a": 1}),
+            ),
+            ("Reasoning: x {\"a\":1}", serde_json::json!({"a": 1})),
+        ] {
+            let value = jsonish::parse(input, options).unwrap();
+            assert_eq!(jsonish::jsonish_to_serde(&value), expected, "{input}");
+        }
+
+        // The reasoning is only searched when isolated, after the answer.
+        let input = "<think>Draft: {\"answer\": 1}</think>{\"answer\": 2}";
+        let options = ParseOptions::default().strip_reasoning(jsonish::ReasoningOptions {
+            mode: jsonish::ReasoningMode::Isolate,
+            ..Default::default()
+        });
+        let value = jsonish::parse(input, options).unwrap();
+        let jsonish::Value::AnyOf(candidates, _) = &value else {
+            panic!("Expected AnyOf, got {value:?}");
+        };
+        let candidates: Vec<_> = candidates.iter().map(jsonish::jsonish_to_serde).collect();
+        assert_eq!(
+            candidates,
+            vec![
+                serde_json::json!({"answer": 2}),
+                serde_json::json!({"answer": 1})
+            ]
+        );
+    }
 }
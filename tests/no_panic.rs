@@ -0,0 +1,123 @@
+//! Hostile and malformed inputs must never make the public API panic.
+//!
+//! Every file in `tests/corpus` is parsed whole and truncated at every
+//! character, the way a streamed response arrives. Inputs that crash the
+//! fuzzer in `fuzz/` belong in the corpus.
+
+use std::{fs, panic, path::Path};
+
+use json_partial::jsonish::{
+    self, coerce_scalars, correct_keys, to_json_string, to_jsonc_string_pretty, CoercionOptions,
+    ExpectedKeys, KeyCorrectionOptions, NormalizeOptions, NumberLocale, ParseOptions, PercentMode,
+    ReasoningMode, ReasoningOptions, Value,
+};
+
+fn options() -> [ParseOptions; 3] {
+    [
+        ParseOptions::default(),
+        ParseOptions::default()
+            .keep_comments(true)
+            .decode_nested_json(3)
+            .normalize_input(NormalizeOptions::default())
+            .strip_reasoning(ReasoningOptions {
+                mode: ReasoningMode::Isolate,
+                ..ReasoningOptions::default()
+            }),
+        ParseOptions::default()
+            .track_hypotheses(4)
+            .interpret_prose(true)
+            .interpret_calls(true)
+            .interpret_yaml(true),
+    ]
+}
+
+fn coercion_options() -> [CoercionOptions; 3] {
+    let mut fraction = CoercionOptions::default().with_locale(NumberLocale::DE);
+    fraction.percent = PercentMode::Fraction;
+    [
+        CoercionOptions::default(),
+        fraction,
+        CoercionOptions::default()
+            .with_locale(NumberLocale::FR)
+            .with_literal("\u{130}", Value::Null),
+    ]
+}
+
+fn exercise(input: &str) {
+    // The input as a scalar, e.g. a string value the parser kept as is
+    for options in coercion_options() {
+        let _ = coerce_scalars(&Value::String(input.to_string()), &options);
+    }
+    let _ = jsonish::extract_tool_calls(input, ParseOptions::default());
+    let _ = jsonish::read_response(input);
+    if let Ok((value, edits)) = jsonish::parse_with_edits(input, ParseOptions::default()) {
+        let _ = jsonish::apply_edits(input, &edits);
+        for edit in &edits {
+            let _ = edit.lsp_range(input);
+        }
+        let _ = jsonish::replace_json(input, &value, &value);
+    }
+    let mut decoder = jsonish::StreamDecoder::new(ParseOptions::default());
+    for chunk in input.as_bytes().chunks(5) {
+        let _ = decoder.push(chunk);
+    }
+    let _ = decoder.finish();
+    for options in options() {
+        let Ok(value) = jsonish::parse(input, options) else {
+            continue;
+        };
+        let _ = to_json_string(&value);
+        let _ = to_jsonc_string_pretty(&value);
+        let _ = value.comments();
+        for options in coercion_options() {
+            let _ = coerce_scalars(&value, &options);
+        }
+        let _ = correct_keys(
+            &value,
+            &ExpectedKeys::new(["name", "a", "b"]),
+            &KeyCorrectionOptions::default(),
+        );
+    }
+}
+
+fn check(name: &str, input: &str) {
+    if panic::catch_unwind(|| exercise(input)).is_err() {
+        panic!("{name}: panicked on {input:?}");
+    }
+}
+
+#[test]
+fn test_corpus_never_panics() {
+    let corpus = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/corpus");
+    let mut files = 0;
+    for entry in fs::read_dir(corpus).unwrap() {
+        let path = entry.unwrap().path();
+        let input = String::from_utf8_lossy(&fs::read(&path).unwrap()).into_owned();
+        let name = path.display().to_string();
+
+        for (end, _) in input.char_indices().skip(1) {
+            check(&name, &input[..end]);
+        }
+        check(&name, &input);
+        files += 1;
+    }
+    assert!(files > 0, "The corpus is empty");
+}
+
+#[test]
+fn test_deep_nesting_never_panics() {
+    for open in ["[", "{\"a\": ", "{a: [", "\"", "```json\n[", "f("] {
+        let input = open.repeat(20_000);
+        check("deep nesting", &input);
+    }
+}
+
+#[test]
+fn test_deep_yaml_never_panics() {
+    let input = format!("a: 1\nb:\n  {}x", "- ".repeat(100_000));
+    check("deep YAML sequence", &input);
+    let input = (0..2_000)
+        .map(|i| format!("{}k:\n", " ".repeat(i)))
+        .collect::<String>();
+    check("deep YAML mapping", &input);
+}
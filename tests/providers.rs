@@ -0,0 +1,156 @@
+//! Provider response bodies, complete and streamed, recorded in
+//! `tests/fixtures/providers`.
+
+use std::{fs, path::Path};
+
+use json_partial::jsonish::{
+    jsonish_to_serde, parse_response, read_response, ParseOptions, Provider, StreamDecoder,
+};
+
+fn fixture(name: &str) -> String {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/providers")
+        .join(name);
+    fs::read_to_string(path).unwrap()
+}
+
+#[test]
+fn test_complete_responses() {
+    for (name, provider, model, finish_reason, expected) in [
+        (
+            "openai_chat.json",
+            Provider::OpenAi,
+            "gpt-4o-2024-08-06",
+            "stop",
+            serde_json::json!({"name": "Alice", "age": 30}),
+        ),
+        (
+            "openai_responses.json",
+            Provider::OpenAi,
+            "gpt-4.1-2025-04-14",
+            "completed",
+            serde_json::json!({"city": "Paris", "temp_c": 18}),
+        ),
+        (
+            "anthropic_message.json",
+            Provider::Anthropic,
+            "claude-sonnet-4-20250514",
+            "end_turn",
+            serde_json::json!({"tags": ["rust", "json"], "count": 2}),
+        ),
+        (
+            "gemini.json",
+            Provider::Gemini,
+            "gemini-2.5-flash",
+            "STOP",
+            serde_json::json!([1, 2, 3, 4]),
+        ),
+    ] {
+        let (response, value) = parse_response(&fixture(name), ParseOptions::default()).unwrap();
+        assert_eq!(response.provider, provider, "{name}");
+        assert_eq!(response.model.as_deref(), Some(model), "{name}");
+        assert_eq!(
+            response.finish_reason.as_deref(),
+            Some(finish_reason),
+            "{name}"
+        );
+        assert!(!response.truncated(), "{name}");
+        assert_eq!(jsonish_to_serde(&value), expected, "{name}");
+    }
+}
+
+#[test]
+fn test_streamed_responses() {
+    for (name, provider, truncated, expected) in [
+        (
+            "openai_chat_stream.txt",
+            Provider::OpenAi,
+            true,
+            serde_json::json!({"items": ["a", "b", "c"]}),
+        ),
+        (
+            "anthropic_stream.txt",
+            Provider::Anthropic,
+            true,
+            serde_json::json!({"steps": ["mix", "bake"]}),
+        ),
+        (
+            "gemini_stream.json",
+            Provider::Gemini,
+            false,
+            serde_json::json!({"ok": true}),
+        ),
+        (
+            "gemini_sse.txt",
+            Provider::Gemini,
+            true,
+            serde_json::json!({"name": "Bob", "age": 42}),
+        ),
+    ] {
+        // The Gemini SSE fixture streams a YAML answer
+        let options = ParseOptions::default().interpret_yaml(true);
+        let (response, value) = parse_response(&fixture(name), options).unwrap();
+        assert_eq!(response.provider, provider, "{name}");
+        assert_eq!(response.truncated(), truncated, "{name}");
+        assert_eq!(jsonish_to_serde(&value), expected, "{name}");
+    }
+}
+
+#[test]
+fn test_cut_stream_has_no_finish_reason() {
+    let body = fixture("anthropic_stream.txt");
+    let cut = &body[..body.find("event: message_delta").unwrap()];
+    let response = read_response(cut).unwrap();
+    assert_eq!(response.text, "{\"steps\": [\"mix\", \"bake");
+    assert_eq!(response.finish_reason, None);
+    assert!(response.truncated());
+
+    assert!(read_response("{\"name\": \"Alice\"}").is_err());
+}
+
+#[test]
+fn test_stream_decoder_snapshots() {
+    for (name, snapshots) in [
+        (
+            "openai_chat_stream.txt",
+            vec![
+                serde_json::json!({"items": ["a"]}),
+                serde_json::json!({"items": ["a", "b", "c"]}),
+            ],
+        ),
+        (
+            "anthropic_stream.txt",
+            vec![
+                serde_json::json!({"steps": ["mix"]}),
+                serde_json::json!({"steps": ["mix", "bake"]}),
+            ],
+        ),
+        (
+            "gemini_json_sse.txt",
+            vec![
+                serde_json::json!({"tags": ["red"]}),
+                serde_json::json!({"tags": ["red", "green"]}),
+            ],
+        ),
+    ] {
+        let body = fixture(name);
+        // Chunks that split lines, and sometimes characters
+        let mut decoder = StreamDecoder::new(ParseOptions::default());
+        let mut found = vec![];
+        for chunk in body.as_bytes().chunks(7) {
+            if let Some(value) = decoder.push(chunk) {
+                let value = jsonish_to_serde(&value);
+                if found.last() != Some(&value) {
+                    found.push(value);
+                }
+            }
+        }
+        assert_eq!(found, snapshots, "{name}");
+        assert_eq!(
+            jsonish_to_serde(&decoder.finish().unwrap()),
+            *snapshots.last().unwrap(),
+            "{name}"
+        );
+        assert!(decoder.response().unwrap().truncated(), "{name}");
+    }
+}